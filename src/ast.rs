@@ -1,4 +1,8 @@
-#[derive(Eq, PartialEq, Debug)]
+use std::fmt::{Display, Formatter};
+use crate::diagnostic::Span;
+use crate::symbols::SymbolTable;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum RegisterPair {
     BC,
     DE,
@@ -15,36 +19,9 @@ impl RegisterPair {
             RegisterPair::SP => "sp",
         }
     }
-
-    pub fn left_table_x_off(&self) -> u8 {
-        match self {
-            RegisterPair::BC => 0x00,
-            RegisterPair::DE => 0x10,
-            RegisterPair::HL => 0x20,
-            RegisterPair::SP => 0x30,
-        }
-    }
-
-    pub fn push_code(&self) -> u8 {
-        match self {
-            RegisterPair::HL => 0xE5,
-            RegisterPair::DE => 0xD5,
-            RegisterPair::BC => 0xC5,
-            RegisterPair::SP => unreachable!(),
-        }
-    }
-
-    pub fn pop_code(&self) -> u8 {
-        match self {
-            RegisterPair::HL => 0xE1,
-            RegisterPair::DE => 0xD1,
-            RegisterPair::BC => 0xC1,
-            RegisterPair::SP => unreachable!(),
-        }
-    }
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum Register {
     A,
     B,
@@ -57,32 +34,6 @@ pub enum Register {
 }
 
 impl Register {
-    pub fn code_off(&self) -> u8 {
-        match self {
-            Register::A => 7,
-            Register::B => 0,
-            Register::C => 1,
-            Register::D => 2,
-            Register::E => 3,
-            Register::H => 4,
-            Register::L => 5,
-            Register::M => 6,
-        }
-    }
-
-    pub fn mov_base_off(&self) -> u8 {
-        match self {
-            Register::A => 0x78,
-            Register::B => 0x40,
-            Register::C => 0x48,
-            Register::D => 0x50,
-            Register::E => 0x58,
-            Register::H => 0x60,
-            Register::L => 0x68,
-            Register::M => 0x70,
-        }
-    }
-
     pub fn name(&self) -> &'static str {
         match self {
             Register::A => "a",
@@ -95,35 +46,219 @@ impl Register {
             Register::M => "m",
         }
     }
+}
 
-    pub fn left_table_x_off(&self) -> u8 {
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BinaryOp {
+    pub fn symbol(&self) -> &'static str {
         match self {
-            Register::A => 0x30,
-            Register::B => 0x00,
-            Register::C => 0x00,
-            Register::D => 0x10,
-            Register::E => 0x10,
-            Register::H => 0x20,
-            Register::L => 0x20,
-            Register::M => 0x30,
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
         }
     }
 
-    pub fn left_table_is_down(&self) -> bool {
+    /// Binding strength, higher binds tighter
+    pub fn precedence(&self) -> u8 {
         match self {
-            Register::B | Register::D | Register::H | Register::M => false,
-            Register::C | Register::E | Register::L | Register::A => true,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 1,
+            BinaryOp::Or => 2,
+            BinaryOp::Xor => 3,
+            BinaryOp::And => 4,
+            BinaryOp::Shl | BinaryOp::Shr => 5,
+            BinaryOp::Add | BinaryOp::Sub => 6,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 7,
+        }
+    }
+
+    pub fn apply(&self, a: i32, b: i32) -> Result<i32, String> {
+        match self {
+            BinaryOp::Add => Ok(a.wrapping_add(b)),
+            BinaryOp::Sub => Ok(a.wrapping_sub(b)),
+            BinaryOp::Mul => Ok(a.wrapping_mul(b)),
+            BinaryOp::Div => a.checked_div(b).ok_or_else(|| "Division by zero".to_owned()),
+            BinaryOp::Mod => a.checked_rem(b).ok_or_else(|| "Division by zero".to_owned()),
+            BinaryOp::And => Ok(a & b),
+            BinaryOp::Or => Ok(a | b),
+            BinaryOp::Xor => Ok(a ^ b),
+            BinaryOp::Shl => Ok(u32::try_from(b).ok().and_then(|b| a.checked_shl(b)).unwrap_or(0)),
+            BinaryOp::Shr => Ok(u32::try_from(b).ok().and_then(|b| a.checked_shr(b)).unwrap_or(0)),
+            // comparisons give 1 when true and 0 otherwise
+            BinaryOp::Eq => Ok((a == b) as i32),
+            BinaryOp::Ne => Ok((a != b) as i32),
+            BinaryOp::Lt => Ok((a < b) as i32),
+            BinaryOp::Le => Ok((a <= b) as i32),
+            BinaryOp::Gt => Ok((a > b) as i32),
+            BinaryOp::Ge => Ok((a >= b) as i32),
+        }
+    }
+}
+
+/// Reason an expression cannot be evaluated
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EvalError {
+    /// Symbol that is not defined, at least not yet
+    Undefined(String),
+    Invalid(String),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::Undefined(name) => write!(f, "Symbol {} is not defined", name),
+            EvalError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Constant expression, evaluated at assembly time
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Num(u16),
+    // `$`, address of the current statement
+    Current,
+    Label(String),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Hi(Box<Expr>),
+    Lo(Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Expr {
+        Expr { kind, span }
+    }
+
+    /// Binary expression spanning both operands
+    pub fn binary(a: Expr, op: BinaryOp, b: Expr) -> Expr {
+        let span = Span::new(a.span.start, b.span.end);
+        Expr::new(ExprKind::Binary(Box::new(a), op, Box::new(b)), span)
+    }
+
+    /// Evaluates the expression; `address` is the value of `$`
+    pub fn eval(&self, address: u16, symbols: &SymbolTable) -> Result<i32, EvalError> {
+        match &self.kind {
+            ExprKind::Num(n) => Ok(*n as i32),
+            ExprKind::Current => Ok(address as i32),
+            ExprKind::Label(label) => symbols.get(label).map(|s| s.value as i32).ok_or_else(|| EvalError::Undefined(label.clone())),
+            ExprKind::Neg(e) => Ok(e.eval(address, symbols)?.wrapping_neg()),
+            ExprKind::Not(e) => Ok(!e.eval(address, symbols)?),
+            ExprKind::Hi(e) => Ok((e.eval(address, symbols)? >> 8) & 0xFF),
+            ExprKind::Lo(e) => Ok(e.eval(address, symbols)? & 0xFF),
+            ExprKind::Binary(a, op, b) => op.apply(a.eval(address, symbols)?, b.eval(address, symbols)?).map_err(EvalError::Invalid),
+        }
+    }
+
+    /// Names of all symbols the expression refers to
+    pub fn labels(&self) -> Vec<&str> {
+        match &self.kind {
+            ExprKind::Num(_) | ExprKind::Current => vec![],
+            ExprKind::Label(label) => vec![label.as_str()],
+            ExprKind::Neg(e) | ExprKind::Not(e) | ExprKind::Hi(e) | ExprKind::Lo(e) => e.labels(),
+            ExprKind::Binary(a, _, b) => {
+                let mut labels = a.labels();
+                labels.extend(b.labels());
+                labels
+            }
+        }
+    }
+
+    /// Whether the expression refers to labels and so is better shown symbolically
+    pub fn is_symbolic(&self) -> bool {
+        match &self.kind {
+            ExprKind::Num(_) | ExprKind::Current => false,
+            ExprKind::Label(_) => true,
+            ExprKind::Neg(e) | ExprKind::Not(e) | ExprKind::Hi(e) | ExprKind::Lo(e) => e.is_symbolic(),
+            ExprKind::Binary(a, _, b) => a.is_symbolic() || b.is_symbolic(),
+        }
+    }
+
+    fn fmt_prec(&self, f: &mut Formatter<'_>, parent: u8) -> std::fmt::Result {
+        match &self.kind {
+            ExprKind::Num(n) if *n < 10 => write!(f, "{}", n),
+            ExprKind::Num(n) => write!(f, "0x{:X}", n),
+            ExprKind::Current => write!(f, "$"),
+            ExprKind::Label(label) => write!(f, "{}", label.strip_prefix('.').unwrap_or(label)),
+            ExprKind::Neg(e) => {
+                write!(f, "-")?;
+                e.fmt_prec(f, u8::MAX)
+            }
+            ExprKind::Not(e) => {
+                write!(f, "~")?;
+                e.fmt_prec(f, u8::MAX)
+            }
+            ExprKind::Hi(e) => write!(f, "hi({})", e),
+            ExprKind::Lo(e) => write!(f, "lo({})", e),
+            ExprKind::Binary(a, op, b) => {
+                let prec = op.precedence();
+                if prec < parent {
+                    write!(f, "(")?;
+                }
+                a.fmt_prec(f, prec)?;
+                write!(f, "{}", op.symbol())?;
+                // operators are left-associative, so an equal right operand needs parentheses
+                b.fmt_prec(f, prec + 1)?;
+                if prec < parent {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_prec(f, 0)
+    }
+}
+
 #[derive(Debug)]
 pub enum MovArg {
     Register(Register),
     RegisterPair(RegisterPair),
-    MemoryDirect(u16),
+    MemoryDirect(Expr),
     MemoryIndirect(RegisterPair),
-    Constant(u16),
+    Constant(Expr),
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -158,38 +293,6 @@ pub enum JmpType {
 }
 
 impl JmpType {
-    pub fn code(&self) -> u8 {
-        match self {
-            JmpType::Jmp => 0xC3,
-            JmpType::Jz => 0xCA,
-            JmpType::Jnz => 0xC2,
-            JmpType::Jc => 0xDA,
-            JmpType::Jnc => 0xD2,
-            JmpType::Jm => 0xFA,
-            JmpType::Jp => 0xF2,
-            JmpType::Jpo => 0xE2,
-            JmpType::Jpe => 0xEA,
-            JmpType::Call => 0xCD,
-            JmpType::Cz => 0xCC,
-            JmpType::Cc => 0xDC,
-            JmpType::Cpe => 0xED,
-            JmpType::Cm => 0xFD,
-            JmpType::Cnz => 0xC4,
-            JmpType::Cnc => 0xD4,
-            JmpType::Cpo => 0xE4,
-            JmpType::Cp => 0xF4,
-            JmpType::Ret => 0xC9,
-            JmpType::Rz => 0xC8,
-            JmpType::Rc => 0xD8,
-            JmpType::Rpe => 0xE8,
-            JmpType::Rm => 0xF8,
-            JmpType::Rnz => 0xC0,
-            JmpType::Rnc => 0xD0,
-            JmpType::Rpo => 0xE0,
-            JmpType::Rp => 0xF0,
-        }
-    }
-
     pub fn name(&self) -> &'static str {
         match self {
             JmpType::Jmp => "jmp",
@@ -237,20 +340,6 @@ pub enum RetType {
 }
 
 impl RetType {
-    pub fn code(&self) -> u8 {
-        match self {
-            RetType::Ret => 0xC9,
-            RetType::Rz => 0xC8,
-            RetType::Rc => 0xD8,
-            RetType::Rpe => 0xE8,
-            RetType::Rm => 0xF8,
-            RetType::Rnz => 0xC0,
-            RetType::Rnc => 0xD0,
-            RetType::Rpo => 0xE0,
-            RetType::Rp => 0xF0,
-        }
-    }
-
     pub fn name(&self) -> &'static str {
         match self {
             RetType::Ret => "ret",
@@ -279,32 +368,6 @@ pub enum ArithmeticType {
 }
 
 impl ArithmeticType {
-    pub fn reg_code(&self) -> u8 {
-        match self {
-            ArithmeticType::Adc => 0x88,
-            ArithmeticType::Add => 0x80,
-            ArithmeticType::Sub => 0x90,
-            ArithmeticType::Sbb => 0x98,
-            ArithmeticType::And => 0xA0,
-            ArithmeticType::Xor => 0xA8,
-            ArithmeticType::Or =>  0xB0,
-            ArithmeticType::Cmp => 0xB8,
-        }
-    }
-
-    pub fn const_code(&self) -> u8 {
-        match self {
-            ArithmeticType::Adc => 0xCE,
-            ArithmeticType::Add => 0xC6,
-            ArithmeticType::Sub => 0xD6,
-            ArithmeticType::Sbb => 0xDE,
-            ArithmeticType::And => 0xE6,
-            ArithmeticType::Xor => 0xEE,
-            ArithmeticType::Or =>  0xF6,
-            ArithmeticType::Cmp => 0xFE,
-        }
-    }
-
     pub fn name(&self) -> &'static str {
         match self {
             ArithmeticType::Adc => "adc",
@@ -332,6 +395,90 @@ impl ArithmeticType {
     }
 }
 
+/// Item of a `db` list
+#[derive(Debug)]
+pub enum Data {
+    Expr(Expr),
+    // every character is stored as a byte with its code
+    Text(String),
+}
+
+impl Data {
+    pub fn size(&self) -> usize {
+        match self {
+            Data::Expr(_) => 1,
+            Data::Text(text) => text.chars().count(),
+        }
+    }
+}
+
+impl Display for Data {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Data::Expr(e) => write!(f, "{}", e),
+            Data::Text(text) => {
+                write!(f, "\"")?;
+                for c in text.chars() {
+                    match c {
+                        '"' | '\\' => write!(f, "\\{}", c)?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        ' '..='~' => write!(f, "{}", c)?,
+                        c => write!(f, "\\x{:02X}", c as u32)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+        }
+    }
+}
+
+/// Byte `db` appends after its items
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Terminator {
+    None,
+    // `dbz`, C strings
+    Zero,
+    // `dbd`, strings printed by the CP/M BDOS function 9
+    Dollar,
+}
+
+impl Terminator {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Terminator::None => "db",
+            Terminator::Zero => "dbz",
+            Terminator::Dollar => "dbd",
+        }
+    }
+
+    pub fn byte(&self) -> Option<u8> {
+        match self {
+            Terminator::None => None,
+            Terminator::Zero => Some(0),
+            Terminator::Dollar => Some(b'$'),
+        }
+    }
+}
+
+/// Order of bytes of a word in memory
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ByteOrder {
+    // low byte first, as the processor reads words
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    pub fn bytes(&self, word: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => word.to_le_bytes(),
+            ByteOrder::Big => word.to_be_bytes(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum StatementKind {
     Nop,
@@ -343,10 +490,10 @@ pub enum StatementKind {
     Stc,
     Cmc,
     Arif(Register, ArithmeticType),
-    Arifn(u16, ArithmeticType),
-    Rst(u8),
-    Out(u8),
-    In(u8),
+    Arifn(Expr, ArithmeticType),
+    Rst(Expr),
+    Out(Expr),
+    In(Expr),
     Neg(Register),
     Inc(Register),
     Incp(RegisterPair),
@@ -356,7 +503,7 @@ pub enum StatementKind {
     Dad(RegisterPair),
     Hlt,
     Pchl,
-    Jmp(String, JmpType),
+    Jmp(Expr, JmpType),
     Ret(RetType),
     Cli,
     Sti,
@@ -364,19 +511,112 @@ pub enum StatementKind {
     Pushpsw,
     Pop(RegisterPair),
     Poppsw,
-    Db(u16),
-    Dw(u16),
-    // set label to specific address
-    Lset(u16),
+    // bytes and strings, followed by the terminator
+    Db(Vec<Data>, Terminator),
+    // words, in the byte order of the compiler dialect unless it is given by `dwle` or `dwbe`
+    Dw(Vec<Expr>, Option<ByteOrder>),
+    // reserved space of the given size, filled only when the fill byte is given
+    Ds(Expr, Option<Expr>),
+    // reserved space up to the next address that is a multiple of the value
+    Align(Expr),
+    // set label to specific address, same as `equ`
+    Lset(Expr),
+    // named constant, the name is stored in the statement label
+    Equ(Expr),
+    // named variable that can be redefined, the name is stored in the statement label
+    Set(Expr),
+    // place following statements at the address
+    Org(Expr),
+    // address where the program starts, written into output formats that support it
+    Entry(Expr),
+    // binary file placed at the address, with an optional offset and length in it,
+    // its size is only known to the compiler, which reads the file
+    Incbin(String, Option<(Expr, Option<Expr>)>),
+    // conditional assembly: statements up to the matching `elif`, `else` or `endif`
+    // are assembled only when the condition holds
+    If(Expr),
+    Elif(Expr),
+    Else,
+    Endif,
+    // `if` checking whether a symbol is defined above
+    Ifdef(String),
+    Ifndef(String),
+    // line with only a label and/or a comment
+    Empty,
+}
+
+impl StatementKind {
+    /// Operand expressions of the statement
+    pub fn expressions(&self) -> Vec<&Expr> {
+        match self {
+            StatementKind::Arifn(e, _) | StatementKind::Rst(e) | StatementKind::Out(e) | StatementKind::In(e) | StatementKind::Jmp(e, _)
+            | StatementKind::Align(e) | StatementKind::Lset(e) | StatementKind::Equ(e) | StatementKind::Set(e)
+            | StatementKind::Org(e) | StatementKind::Entry(e) | StatementKind::If(e) | StatementKind::Elif(e) => vec![e],
+            StatementKind::Mov(a, b) => [a, b].into_iter()
+                .filter_map(|x| match x {
+                    MovArg::MemoryDirect(e) | MovArg::Constant(e) => Some(e),
+                    _ => None,
+                })
+                .collect(),
+            StatementKind::Incbin(_, Some((offset, length))) | StatementKind::Ds(offset, length) => std::iter::once(offset).chain(length).collect(),
+            StatementKind::Db(items, _) => items.iter()
+                .filter_map(|x| match x {
+                    Data::Expr(e) => Some(e),
+                    Data::Text(_) => None,
+                })
+                .collect(),
+            StatementKind::Dw(items, _) => items.iter().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether the statement is one of the conditional assembly directives
+    pub fn is_conditional(&self) -> bool {
+        matches!(self, StatementKind::If(_) | StatementKind::Elif(_) | StatementKind::Else | StatementKind::Endif
+            | StatementKind::Ifdef(_) | StatementKind::Ifndef(_))
+    }
+
+    /// Whether the statement places data rather than a processor instruction
+    pub fn is_data(&self) -> bool {
+        matches!(self, StatementKind::Db(_, _) | StatementKind::Dw(_, _) | StatementKind::Ds(_, _) | StatementKind::Align(_)
+            | StatementKind::Incbin(_, _))
+    }
+
+    /// Size of the encoded statement in bytes, known before any label is resolved.
+    /// Sizes of `incbin`, `ds` and `align` are found by the compiler, here they are 0
+    pub fn size(&self) -> u16 {
+        match self {
+            StatementKind::Lset(_) | StatementKind::Equ(_) | StatementKind::Set(_) | StatementKind::Org(_) | StatementKind::Entry(_) | StatementKind::Empty => 0,
+            StatementKind::Incbin(_, _) | StatementKind::Ds(_, _) | StatementKind::Align(_) => 0,
+            kind if kind.is_conditional() => 0,
+            StatementKind::Arifn(_, _) | StatementKind::In(_) | StatementKind::Out(_) => 2,
+            StatementKind::Jmp(_, _) => 3,
+            StatementKind::Db(items, terminator) => {
+                let size: usize = items.iter().map(Data::size).sum();
+                (size + terminator.byte().is_some() as usize) as u16
+            }
+            StatementKind::Dw(items, _) => 2 * items.len() as u16,
+            StatementKind::Mov(a, b) => match (a, b) {
+                (MovArg::MemoryDirect(_), _) | (_, MovArg::MemoryDirect(_)) => 3,
+                (MovArg::RegisterPair(_), MovArg::Constant(_)) => 3,
+                (MovArg::Register(_), MovArg::Constant(_)) => 2,
+                _ => 1,
+            },
+            _ => 1,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Statement {
     pub label: Option<String>,
     pub kind: StatementKind,
+    /// Text of the `;` or `//` comment ending the line, block comments are not kept
+    pub comment: Option<String>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct File {
-    pub statements: Vec<Box<Statement>>,
+    pub statements: Vec<Statement>,
 }
\ No newline at end of file
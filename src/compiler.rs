@@ -1,320 +1,864 @@
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
-use crate::ast::{File, Statement, StatementKind, Register, RegisterPair, MovArg};
+use std::path::PathBuf;
+use crate::ast::{ByteOrder, Data, EvalError, File, Statement, StatementKind, Register, RegisterPair, MovArg, Expr, Terminator};
+use crate::diagnostic::{Diagnostic, Diagnostics, Severity, Source, Span};
+use crate::{parser, preprocessor};
+use crate::image::Image;
+use crate::opcodes::{self, Operand};
+use crate::symbols::{Symbol, SymbolKind, SymbolTable};
+use crate::target::Target;
+
+/// Statement of the source together with the result of its compilation
+#[derive(Clone, Debug)]
+pub struct CompiledStatement {
+    pub span: Span,
+    /// Address of the statement, for `org` the new address
+    pub address: u16,
+    pub code: Vec<u8>,
+    /// Value of the symbol defined by `equ`, `set` or `lset`
+    pub value: Option<u16>,
+    /// Whether the code is a processor instruction rather than data
+    pub instruction: bool,
+}
+
+/// Row of the instruction table
+#[derive(Clone, Debug)]
+pub struct Row {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    /// Label of the statement or of the label-only line before it
+    pub label: Option<String>,
+    pub mnemonic: String,
+    pub operands: String,
+    pub comment: Option<String>,
+    /// 1-based number of the source line
+    pub line: usize,
+    pub source: String,
+}
+
+impl Row {
+    /// Instruction as written in the table, e.g. `mvi b, 3F`
+    pub fn asm(&self) -> String {
+        if self.operands.is_empty() {
+            self.mnemonic.clone()
+        } else {
+            format!("{} {}", self.mnemonic, self.operands)
+        }
+    }
+}
 
 pub struct CompilerResult {
-    pub data: Vec<u8>,
-    pub pretty_instructions: Vec<String>,
-    pub table: Vec<String>,
+    origin: u16,
+    image: Image,
+    statements: Vec<CompiledStatement>,
+    enabled: Vec<bool>,
+    entry: Option<u16>,
+    pretty_instructions: Vec<String>,
+    rows: Vec<Row>,
+    symbols: Vec<Symbol>,
+    diagnostics: Diagnostics,
+}
+
+impl CompilerResult {
+    /// Address of the first statement, set by `Compiler::origin` or by the target
+    pub fn origin(&self) -> u16 {
+        self.origin
+    }
+
+    /// Compiled program placed at its addresses
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Memory from the lowest to the highest used address, gaps between `org` blocks are zero
+    pub fn bytes(&self) -> Vec<u8> {
+        match (self.image.start(), self.image.end()) {
+            (Some(start), Some(end)) => self.image.slice(start as u32, end, 0),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Start address set by `entry`
+    pub fn entry(&self) -> Option<u16> {
+        self.entry
+    }
+
+    /// Every statement in source order, except lines with only a comment
+    pub fn statements(&self) -> &[CompiledStatement] {
+        &self.statements
+    }
+
+    /// Whether every statement of the file was assembled, false inside of conditional blocks whose condition does not hold
+    pub fn enabled(&self) -> &[bool] {
+        &self.enabled
+    }
+
+    /// Table rows of statements that produce code
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    /// Labels and constants ordered by name
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Symbol by name, with or without the leading dot
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        let name = name.strip_prefix('.').unwrap_or(name);
+        self.symbols.iter().find(|s| s.name == name)
+    }
+
+    /// Warnings produced during compilation
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
 }
 
 impl Debug for CompilerResult {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "CompilerResult ( {:?} ): [\n", &self.pretty_instructions)?;
-        for x in &self.table {
-            writeln!(f, "{}", x)?;
+        writeln!(f, "CompilerResult ( {:?} ): [", &self.pretty_instructions)?;
+        for x in &self.rows {
+            writeln!(f, "{:04X} {:02X?} {}", x.address, x.bytes, x.asm())?;
         }
         writeln!(f, "]\n")
     }
 }
 
-pub struct Compiler {}
+/// Default load address of the training stand
+pub const DEFAULT_ORIGIN: u16 = 0x8200;
 
-impl Compiler {
-    pub fn new() -> Compiler {
-        Compiler {}
+/// Error of a statement, located at the operand that caused it if there is one
+struct StatementError {
+    span: Option<Span>,
+    message: String,
+    /// Set when an operand refers to a symbol that is not defined
+    undefined: bool,
+}
+
+impl StatementError {
+    /// Error located at an operand
+    fn at(span: Span, message: String) -> StatementError {
+        StatementError { span: Some(span), message, undefined: false }
     }
 
-    pub fn compile(&self, tokens: &File) -> CompilerResult {
-        let mut labels = HashMap::<String, u16>::new();
-        let mut pretty_out = Vec::<String>::new();
-        let mut data = Vec::<u8>::new();
-        let mut table = Vec::<String>::new();
-        let mut code_ptr = 0x8200u16;
-
-        for (idx, statement) in tokens.statements.iter().enumerate() {
-            if let StatementKind::Lset(addr) = &statement.kind {
-                if let Some(label) = &statement.label {
-                    labels.insert(label.to_owned(), *addr);
-                    labels.insert(format!(".{}", label), *addr);
-                } else {
-                    println!("WARNING! No label at lset at pos {}", idx + 1);
-                }
-                continue;
-            }
-            if let Some(label) = &statement.label {
-                labels.insert(label.to_owned(), code_ptr);
-                labels.insert(format!(".{}", label), code_ptr);
-            }
-            if let Some((code, pretty)) = Self::compile_statement(&statement, &labels) {
-                pretty_out.push(pretty.to_uppercase());
-                let mut code_str = String::new();
-                for b in &code {
-                    data.push(*b);
-                    code_str += &format!("{:02X} ", b);
-                }
-                table.push(format!("{:04X};{};{};{}", code_ptr, code_str, &statement.label.clone().unwrap_or("".to_owned()), pretty));
-                code_ptr += code.len() as u16;
-            }
+    /// Appends an explanation to the message of an undefined symbol error, other errors are kept as they are
+    fn explain_undefined(mut self, text: &str) -> StatementError {
+        if self.undefined {
+            self.message = format!("{}, {}", self.message, text);
         }
-        CompilerResult { pretty_instructions: pretty_out, data, table }
+        self
+    }
+
+    fn diagnostic(self, source: &Source, statement: &Statement) -> Diagnostic {
+        source.error(self.span.unwrap_or(statement.span), self.message)
     }
+}
+
+impl From<String> for StatementError {
+    fn from(message: String) -> Self {
+        StatementError { span: None, message, undefined: false }
+    }
+}
+
+/// State needed to evaluate operands of a single statement
+struct Context<'a> {
+    symbols: &'a SymbolTable,
+    /// Address of the statement, value of `$`
+    address: u16,
+}
 
-    fn format_label(label: &str) -> &str {
-        if label.starts_with(".") {
-            &label[1..]
+impl Context<'_> {
+    /// Operand as shown in the table: symbolic if it refers to labels, `value` otherwise
+    fn show(expr: &Expr, value: String) -> String {
+        if expr.is_symbolic() {
+            expr.to_string()
         } else {
-            label
+            value
         }
     }
 
-    fn prepend_to_addr(val: u8, addr: u16) -> Vec<u8> {
-        let mut vec = Vec::new();
-        vec.push(val);
-        vec.extend_from_slice(&addr.to_le_bytes());
-        vec
+    fn show_byte(&self, expr: &Expr) -> Result<String, StatementError> {
+        Ok(Self::show(expr, format!("{:02X}", self.byte(expr)?)))
     }
 
-    fn check_8bit_const(c: &u16) -> Option<()> {
-        if *c > 255u16 {
-            eprintln!("Cannot add two-byte constant");
-            None
+    fn show_word(&self, expr: &Expr) -> Result<String, StatementError> {
+        Ok(Self::show(expr, format!("{:04X}", self.word(expr)?)))
+    }
+
+    /// Evaluates the expression, errors are located at it
+    fn eval(&self, expr: &Expr) -> Result<i32, StatementError> {
+        expr.eval(self.address, self.symbols).map_err(|e| StatementError {
+            span: Some(expr.span),
+            message: e.to_string(),
+            undefined: matches!(e, EvalError::Undefined(_)),
+        })
+    }
+
+    /// Evaluates an 8-bit operand, negative values are stored in two's complement
+    fn byte(&self, expr: &Expr) -> Result<u8, StatementError> {
+        let value = self.eval(expr)?;
+        if (-128..=255).contains(&value) {
+            Ok(value as u8)
         } else {
-            Some(())
+            Err(StatementError::at(expr.span, format!("Value {} does not fit into 8 bits", value)))
         }
     }
 
-    fn compile_statement(statement: &Statement, label_map: &HashMap<String, u16>) -> Option<(Vec<u8>, String)> {
-        match &statement.kind {
-            StatementKind::Lset(_) => None,
-            StatementKind::Cmc => Some((vec![0x3f], "cmc".to_owned())),
-            StatementKind::Nop => Some((vec![0x00], "nop".to_owned())),
-            StatementKind::Ral => Some((vec![0x17], "ral".to_owned())),
-            StatementKind::Rar => Some((vec![0x1F], "rar".to_owned())),
-            StatementKind::Rlc => Some((vec![0x07], "rlc".to_owned())),
-            StatementKind::Rrc => Some((vec![0x0F], "rrc".to_owned())),
-            StatementKind::Stc => Some((vec![0x37], "stc".to_owned())),
-            StatementKind::Daa => Some((vec![0x27], "daa".to_owned())),
-            StatementKind::Hlt => Some((vec![0x76], "hlt".to_owned())),
-            StatementKind::Cli => Some((vec![0xF3], "di".to_owned())),
-            StatementKind::Sti => Some((vec![0xFB], "ei".to_owned())),
-            StatementKind::Pchl => Some((vec![0xE9], "pchl".to_owned())),
-            StatementKind::In(port) => Some((vec![0xDB, *port], format!("in {}", port))),
-            StatementKind::Out(port) => Some((vec![0xD3, *port], format!("out {}", port))),
-            StatementKind::Db(dat) => Self::check_8bit_const(dat).map(|_| (vec![*dat as u8], format!("{:02X}", dat))),
-            StatementKind::Dw(dat) => Some((dat.to_be_bytes().to_vec(), format!("{:04X}", dat))),
-            StatementKind::Jmp(label, typ) => {
-                let addr = *label_map.get(label).expect(&format!("Label {} not found", label));
-                Some((Self::prepend_to_addr(typ.code(), addr), format!("{} {}", typ.name(), Self::format_label(label))))
+    /// Evaluates a 16-bit operand, negative values are stored in two's complement
+    fn word(&self, expr: &Expr) -> Result<u16, StatementError> {
+        let value = self.eval(expr)?;
+        if (-32768..=65535).contains(&value) {
+            Ok(value as u16)
+        } else {
+            Err(StatementError::at(expr.span, format!("Value {} does not fit into 16 bits", value)))
+        }
+    }
+}
+
+/// Variant of the language, selects the byte order of words stored by `dw`
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Dialect {
+    /// Words are stored low byte first, as the processor reads them
+    Kr580,
+    /// Words are stored high byte first, as older versions of the compiler did
+    Legacy,
+}
+
+impl Dialect {
+    /// Byte order of words stored by `dw`
+    pub fn word_order(&self) -> ByteOrder {
+        match self {
+            Dialect::Kr580 => ByteOrder::Little,
+            Dialect::Legacy => ByteOrder::Big,
+        }
+    }
+}
+
+/// What is done with warnings
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Warnings {
+    /// Drop them
+    Allow,
+    /// Report them in `CompilerResult::diagnostics`
+    Warn,
+    /// Report them as errors, failing the compilation
+    Deny,
+}
+
+/// Result of the first pass
+struct Layout {
+    symbols: SymbolTable,
+    /// Address of every statement
+    addresses: Vec<u16>,
+    /// Whether every statement is assembled, false inside of conditional blocks whose condition does not hold
+    enabled: Vec<bool>,
+    /// Contents of `incbin` files by statement index
+    binaries: HashMap<usize, Vec<u8>>,
+    /// Sizes of `ds` and `align` by statement index
+    reserved: HashMap<usize, u16>,
+}
+
+/// Open `if` block of conditional assembly
+struct Conditional {
+    span: Span,
+    /// Whether the code around the block is assembled
+    outer: bool,
+    /// Whether the current branch is assembled
+    active: bool,
+    /// Whether one of the branches was already assembled
+    taken: bool,
+    else_span: Option<Span>,
+}
+
+pub struct Compiler {
+    /// Set by `origin`, the origin of the target otherwise
+    origin: Option<u16>,
+    target: Target,
+    dialect: Dialect,
+    warnings: Warnings,
+    include_paths: Vec<PathBuf>,
+    defines: Vec<(String, u16)>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler { origin: None, target: Target::Stand, dialect: Dialect::Kr580, warnings: Warnings::Warn, include_paths: Vec::new(), defines: Vec::new() }
+    }
+
+    pub fn dialect(mut self, dialect: Dialect) -> Compiler {
+        self.dialect = dialect;
+        self
+    }
+
+    pub fn warnings(mut self, warnings: Warnings) -> Compiler {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Preprocesses, parses and compiles the text, diagnostics refer to it as `<input>`,
+    /// included files are searched in the current directory and the include paths
+    pub fn assemble(&self, text: &str) -> Result<CompilerResult, Diagnostics> {
+        self.assemble_source(&Source::new("<input>".to_owned(), text.to_owned())).map(|(_, _, result)| result)
+    }
+
+    /// Preprocesses, parses and compiles the source. The preprocessed source and the parsed file
+    /// are returned together with the result, as the listing refers to them
+    pub fn assemble_source(&self, source: &Source) -> Result<(Source, File, CompilerResult), Diagnostics> {
+        let source = preprocessor::expand(source, &self.include_paths)?;
+        let file = parser::parse(&source)?;
+        let result = self.compile(&file, &source)?;
+        Ok((source, file, result))
+    }
+
+    /// Sets the machine the program is written for, which predefines its symbols
+    /// and the origin, unless it is set with `origin`
+    pub fn target(mut self, target: Target) -> Compiler {
+        self.target = target;
+        self
+    }
+
+    /// Address of the first statement
+    fn origin_address(&self) -> u16 {
+        self.origin.unwrap_or_else(|| self.target.origin())
+    }
+
+    /// Adds a directory searched for `include` and `incbin` files that are not found next to the file using them
+    pub fn include_path(mut self, path: impl Into<PathBuf>) -> Compiler {
+        self.include_paths.push(path.into());
+        self
+    }
+
+    /// Predefines a symbol, e.g. to select a branch of conditional assembly. Like the symbols of the target,
+    /// it can be redefined by the program
+    pub fn define(mut self, name: &str, value: u16) -> Compiler {
+        self.defines.push((name.to_owned(), value));
+        self
+    }
+
+    /// Sets the address of the first statement, used until the first `org`
+    pub fn origin(mut self, origin: u16) -> Compiler {
+        self.origin = Some(origin);
+        self
+    }
+
+    pub fn compile(&self, tokens: &File, source: &Source) -> Result<CompilerResult, Diagnostics> {
+        let mut diagnostics = Diagnostics::new();
+        let Layout { mut symbols, addresses, enabled, mut binaries, reserved } = self.layout(tokens, source, &mut diagnostics);
+        let mut pretty_out = Vec::<String>::new();
+        let mut image = Image::new();
+        let mut rows = Vec::<Row>::new();
+        // address, size and location of every emitted statement, used to find overlapping code
+        let mut chunks = Vec::new();
+        let mut entry: Option<(u16, Span)> = None;
+        let mut compiled = Vec::<CompiledStatement>::with_capacity(addresses.len());
+
+        // label of a label-only line, shown on the next row of the table
+        let mut pending_label: Option<&String> = None;
+        for (index, (statement, &code_ptr)) in tokens.statements.iter().zip(&addresses).enumerate() {
+            let blank = matches!(statement.kind, StatementKind::Empty) || statement.kind.is_conditional();
+            if !enabled[index] || blank && statement.label.is_none() {
+                continue;
             }
-            StatementKind::Ret(typ) => Some((vec![typ.code()], format!("{}", typ.name()))),
-            StatementKind::Rst(code) => Self::compile_rst(*code),
-            StatementKind::Push(pair) => {
-                if pair == &RegisterPair::SP {
-                    eprintln!("Cannot push SP onto stack");
-                    None
-                } else {
-                    Some((vec![pair.push_code()], format!("push {}", pair.name())))
+            compiled.push(CompiledStatement {
+                span: statement.span,
+                address: code_ptr,
+                code: vec![],
+                value: None,
+                instruction: !statement.kind.is_data(),
+            });
+            match &statement.kind {
+                StatementKind::Lset(_) | StatementKind::Equ(_) => {
+                    let value = statement.label.as_ref().and_then(|name| symbols.get(name)).map(|s| s.value);
+                    compiled.last_mut().unwrap().value = value;
+                    continue;
                 }
-            },
-            StatementKind::Pop(pair) => {
-                if pair == &RegisterPair::SP {
-                    eprintln!("Cannot push SP onto stack");
-                    None
-                } else {
-                    Some((vec![pair.pop_code()], format!("pop {}", pair.name())))
+                StatementKind::Org(_) => continue,
+                kind if kind.is_conditional() => continue,
+                StatementKind::Set(expr) => {
+                    // variables have the value of the last `set` before the statement that uses them,
+                    // errors were already reported by the layout pass
+                    let ctx = Context { symbols: &symbols, address: code_ptr };
+                    if let (Some(name), Ok(value)) = (&statement.label, ctx.word(expr)) {
+                        let _ = symbols.define(name, SymbolKind::Variable, value, statement.span);
+                        compiled.last_mut().unwrap().value = Some(value);
+                    }
+                    continue;
                 }
-            }
-            StatementKind::Pushpsw => Some((vec![0xF5], format!("push psw"))),
-            StatementKind::Poppsw => Some((vec![0xF1], format!("pop psw"))),
-            StatementKind::Arif(reg, typ) => Some((vec![typ.reg_code() + reg.code_off()], format!("{} {}", typ.name(), reg.name()))),
-            StatementKind::Arifn(c, typ) => Self::check_8bit_const(c).map(|_| (vec![typ.const_code(), *c as u8], format!("{} {:02X}", typ.const_name(), c))),
-            StatementKind::Dad(pair) => Some((vec![0x09 + pair.left_table_x_off()], format!("dat {}", pair.name()))),
-            StatementKind::Incp(pair) => Some((vec![pair.left_table_x_off() + 0x03], format!("inx {}", pair.name()))),
-            StatementKind::Dcrp(pair) => Some((vec![pair.left_table_x_off() + 0x0B], format!("dcx {}", pair.name()))),
-            StatementKind::Inc(reg) => {
-                let cmd = if reg.left_table_is_down() { 0x0C } else { 0x04 } + reg.left_table_x_off();
-                Some((vec![cmd], format!("inr {}", reg.name())))
-            },
-            StatementKind::Dcr(reg) => {
-                let cmd = if reg.left_table_is_down() { 0x0D } else { 0x05 } + reg.left_table_x_off();
-                Some((vec![cmd], format!("dcr {}", reg.name())))
-            },
-            StatementKind::Neg(reg) => {
-                match reg {
-                    Register::A => Some((vec![0x2F], format!("cma"))),
-                    Register::C => Some((vec![0x3F], format!("cmc"))),
-                    _ => {
-                        eprintln!("Neg is not supported for register {}", reg.name());
-                        None
+                StatementKind::Empty => {
+                    if statement.label.is_some() {
+                        pending_label = statement.label.as_ref();
                     }
+                    continue;
                 }
-            },
-            StatementKind::Mov(a, b) => {
-                match a {
-                    MovArg::Constant(_) => {
-                        eprintln!("Cannot move into constant");
-                        None
-                    },
-                    MovArg::Register(a_reg) => Self::compile_mov_register(a_reg, b),
-                    MovArg::RegisterPair(pair) => Self::compile_mov_regpair(pair, b),
-                    MovArg::MemoryIndirect(pair) => Self::compile_mov_mem_indirect(b, pair),
-                    MovArg::MemoryDirect(mem) => Self::compile_mov_mem_direct(mem, b),
+                StatementKind::Entry(expr) => {
+                    let ctx = Context { symbols: &symbols, address: code_ptr };
+                    match (ctx.word(expr), entry) {
+                        (Err(e), _) => diagnostics.push(e.diagnostic(source, statement)),
+                        (Ok(_), Some((_, previous))) => {
+                            diagnostics.push(source.error(statement.span, "Entry point is set multiple times".to_owned())
+                                .with_note(source.note(previous, "previous entry point is here".to_owned())));
+                        }
+                        (Ok(address), None) => entry = Some((address, statement.span)),
+                    }
+                    continue;
                 }
+                _ => {}
+            }
+            let label = statement.label.as_ref().or(pending_label.take());
+            let ctx = Context { symbols: &symbols, address: code_ptr };
+            // sizes of these statements are found by the layout pass, which also reports their errors
+            let encoded = match &statement.kind {
+                StatementKind::Incbin(name, _) => match binaries.remove(&index) {
+                    Some(data) => Ok((data, format!("incbin \"{}\"", name))),
+                    None => continue,
+                },
+                StatementKind::Ds(_, _) | StatementKind::Align(_) => match reserved.get(&index) {
+                    Some(&size) => Self::reserve(statement, size, &ctx),
+                    None => continue,
+                },
+                _ => Self::compile_statement(statement, &ctx, self.dialect),
+            };
+            match encoded {
+                Ok((code, _)) if code_ptr as usize + code.len() > 0x10000 => {
+                    diagnostics.push(source.error(statement.span, format!("Code at {:04X} does not fit into memory", code_ptr)));
+                }
+                Ok((code, pretty)) => {
+                    pretty_out.push(pretty.to_uppercase());
+                    image.write(code_ptr, &code);
+                    compiled.last_mut().unwrap().code = code.clone();
+                    chunks.push((code_ptr, code.len(), statement.span));
+                    let (mnemonic, operands) = pretty.split_once(' ').unwrap_or((&pretty, ""));
+                    rows.push(Row {
+                        address: code_ptr,
+                        bytes: code,
+                        label: label.cloned(),
+                        mnemonic: mnemonic.to_owned(),
+                        operands: operands.to_owned(),
+                        comment: statement.comment.clone(),
+                        line: source.line(statement.span.start),
+                        source: source.line_text(statement.span.start).to_owned(),
+                    });
+                }
+                Err(e) => diagnostics.push(e.diagnostic(source, statement)),
             }
         }
+        Self::check_overlaps(chunks, source, &mut diagnostics);
+        match self.warnings {
+            Warnings::Allow => diagnostics.retain(|d| d.severity != Severity::Warning),
+            Warnings::Warn => {}
+            Warnings::Deny => diagnostics.deny_warnings(),
+        }
+        diagnostics.sort();
+        if diagnostics.has_errors() {
+            return Err(diagnostics);
+        }
+        image.normalize();
+        Ok(CompilerResult {
+            origin: self.origin_address(),
+            pretty_instructions: pretty_out,
+            image,
+            statements: compiled,
+            enabled,
+            entry: entry.map(|(address, _)| address),
+            rows,
+            symbols: symbols.sorted(),
+            diagnostics,
+        })
     }
 
-    fn compile_rst(code: u8) -> Option<(Vec<u8>, String)> {
-        match code {
-            0 => Some((vec![0xC7], "rst 0".to_owned())),
-            16 => Some((vec![0xD7], "rst 16".to_owned())),
-            32 => Some((vec![0xE7], "rst 32".to_owned())),
-            48 => Some((vec![0xF7], "rst 48".to_owned())),
-            8 => Some((vec![0xCF], "rst 8".to_owned())),
-            24 => Some((vec![0xDF], "rst 24".to_owned())),
-            40 => Some((vec![0xEF], "rst 40".to_owned())),
-            56 => Some((vec![0xFF], "rst 56".to_owned())),
-            _ => {
-                eprintln!("RST code {} not supported", code);
-                None
+    /// Reports statements placed by `org` over memory already used by other statements
+    fn check_overlaps(mut chunks: Vec<(u16, usize, Span)>, source: &Source, diagnostics: &mut Diagnostics) {
+        chunks.retain(|(_, len, _)| *len > 0);
+        chunks.sort_by_key(|(address, _, span)| (*address, span.start));
+        // chunk reaching the furthest so far
+        let mut furthest: Option<(u32, Span)> = None;
+        for (address, len, span) in chunks {
+            let end = address as u32 + len as u32;
+            match furthest {
+                Some((furthest_end, other)) if furthest_end > address as u32 => {
+                    diagnostics.push(source.error(span, format!("Code at {:04X} overlaps with code placed earlier", address))
+                        .with_note(source.note(other, "overlapping code is here".to_owned())));
+                    if end > furthest_end {
+                        furthest = Some((end, span));
+                    }
+                }
+                _ => furthest = Some((end, span)),
             }
         }
     }
 
-    fn compile_mov_mem_direct(mem: &u16, b: &MovArg) -> Option<(Vec<u8>, String)> {
-        if let MovArg::Register(reg) = b {
-            if reg != &Register::A {
-                eprintln!("Cannot store register {} into memory", reg.name());
-                None
-            } else {
-                Some((Self::prepend_to_addr(0x32, *mem), format!("sta {:04X}", mem)))
+    /// First pass: assign an address to every statement and collect all symbols,
+    /// so that the second pass can resolve references to symbols defined later.
+    /// Conditions of conditional assembly are evaluated and files of `incbin` are read here
+    fn layout<'a>(&self, tokens: &'a File, source: &Source, diagnostics: &mut Diagnostics) -> Layout {
+        let mut symbols = SymbolTable::new();
+        for (name, value) in self.target.symbols().iter().copied().chain(self.defines.iter().map(|(n, v)| (n.as_str(), *v))) {
+            let _ = symbols.define(name, SymbolKind::Builtin, value, Span::default());
+        }
+        let mut addresses = Vec::with_capacity(tokens.statements.len());
+        let mut enabled = Vec::with_capacity(tokens.statements.len());
+        let mut conditionals = Vec::<Conditional>::new();
+        // constants referring to symbols defined later, with their addresses
+        let mut pending = Vec::<(&'a Statement, u16)>::new();
+        let mut binaries = HashMap::new();
+        let mut reserved = HashMap::new();
+        let mut code_ptr = self.origin_address();
+
+        for (index, statement) in tokens.statements.iter().enumerate() {
+            // directives belong to the enclosing block, so their labels are defined when it is assembled
+            let active = match &statement.kind {
+                StatementKind::Elif(_) | StatementKind::Else | StatementKind::Endif => conditionals.last().is_none_or(|c| c.outer),
+                _ => conditionals.last().is_none_or(|c| c.active),
+            };
+            if statement.kind.is_conditional() {
+                let ctx = Context { symbols: &symbols, address: code_ptr };
+                Self::conditional(statement, &ctx, &mut conditionals, source, diagnostics);
             }
-        } else if let MovArg::RegisterPair(pair) = b {
-            if pair != &RegisterPair::HL {
-                eprintln!("Storing value from pair {} is not supported", pair.name());
-                None
-            } else {
-                Some((Self::prepend_to_addr(0x22, *mem), format!("shld {:04X}", mem)))
+            enabled.push(active);
+            if !active {
+                addresses.push(code_ptr);
+                continue;
             }
-        } else {
-            eprintln!("Cannot load something in direct memory other than registers");
-            None
+            if let StatementKind::Org(expr) = &statement.kind {
+                match (Context { symbols: &symbols, address: code_ptr }).word(expr) {
+                    Ok(address) => code_ptr = address,
+                    Err(e) => diagnostics.push(e.explain_undefined("org can only use symbols defined above it").diagnostic(source, statement)),
+                }
+            }
+            addresses.push(code_ptr);
+            let ctx = Context { symbols: &symbols, address: code_ptr };
+            match (&statement.kind, &statement.label) {
+                (StatementKind::Lset(_), None) => {
+                    diagnostics.push(source.warning(statement.span, "lset without a label has no effect".to_owned()));
+                }
+                (StatementKind::Lset(expr) | StatementKind::Equ(expr), Some(name)) => {
+                    match ctx.word(expr) {
+                        Ok(value) => Self::define_symbol(&mut symbols, name, SymbolKind::Constant, value, statement, source, diagnostics),
+                        Err(_) => pending.push((statement, code_ptr)),
+                    }
+                }
+                (StatementKind::Set(expr), Some(name)) => {
+                    match ctx.word(expr) {
+                        Ok(value) => Self::define_symbol(&mut symbols, name, SymbolKind::Variable, value, statement, source, diagnostics),
+                        Err(e) => diagnostics.push(e.diagnostic(source, statement)),
+                    }
+                }
+                (_, Some(name)) => Self::define_symbol(&mut symbols, name, SymbolKind::Label, code_ptr, statement, source, diagnostics),
+                (_, None) => {}
+            }
+            let size = match &statement.kind {
+                StatementKind::Incbin(name, range) => match self.incbin(name, range, &Context { symbols: &symbols, address: code_ptr }, source.file_name(statement.span.start)) {
+                    Ok(data) => {
+                        let size = data.len() as u16;
+                        binaries.insert(index, data);
+                        size
+                    }
+                    Err(e) => {
+                        diagnostics.push(e.diagnostic(source, statement));
+                        0
+                    }
+                },
+                StatementKind::Ds(_, _) | StatementKind::Align(_) => match Self::reserved_size(&statement.kind, &Context { symbols: &symbols, address: code_ptr }) {
+                    Ok(size) if code_ptr as u32 + size as u32 > 0x10000 => {
+                        diagnostics.push(source.error(statement.span, format!("Reserved space at {:04X} does not fit into memory", code_ptr)));
+                        0
+                    }
+                    Ok(size) => {
+                        reserved.insert(index, size);
+                        size
+                    }
+                    Err(e) => {
+                        diagnostics.push(e.diagnostic(source, statement));
+                        0
+                    }
+                },
+                kind => kind.size(),
+            };
+            code_ptr = code_ptr.wrapping_add(size);
         }
-    }
 
-    fn compile_mov_mem_indirect(b: &MovArg, pair: &RegisterPair) -> Option<(Vec<u8>, String)> {
-        if let MovArg::Register(b_reg) = b {
-            if b_reg != &Register::A {
-                eprintln!("Cannot store register {} into indirect memory", b_reg.name());
-                None
-            } else {
-                match pair {
-                    RegisterPair::BC => Some((vec![0x02], "stax b".to_owned())),
-                    RegisterPair::DE => Some((vec![0x12], "stax d".to_owned())),
-                    RegisterPair::HL | RegisterPair::SP => {
-                        eprintln!("Cannot load register {} from indirect memory at {}", b_reg.name(), pair.name());
-                        None
+        // all labels are known now, so constants are resolved in as many rounds as needed
+        while !pending.is_empty() {
+            let mut unresolved = Vec::new();
+            for (statement, address) in pending.iter().copied() {
+                let (StatementKind::Lset(expr) | StatementKind::Equ(expr)) = &statement.kind else { continue };
+                let ctx = Context { symbols: &symbols, address };
+                match ctx.word(expr) {
+                    Ok(value) => {
+                        let name = statement.label.as_ref().unwrap();
+                        Self::define_symbol(&mut symbols, name, SymbolKind::Constant, value, statement, source, diagnostics);
                     }
+                    Err(e) => unresolved.push((statement, address, e)),
                 }
             }
-        } else if let MovArg::RegisterPair(b_pair) = b {
-            if pair == &RegisterPair::SP && b_pair == &RegisterPair::HL {
-                Some((vec![0xE3], format!("xthl")))
-            } else {
-                eprintln!("Indirect memory access is supported only for HL from SP");
-                None
+            if unresolved.len() == pending.len() {
+                for (statement, _, e) in unresolved {
+                    diagnostics.push(e.diagnostic(source, statement));
+                }
+                break;
             }
-        } else {
-            eprintln!("Indirect moving into memory is supported only for registers");
-            None
+            pending = unresolved.into_iter().map(|(statement, address, _)| (statement, address)).collect();
+        }
+        for x in conditionals {
+            diagnostics.push(source.error(x.span, "Conditional block is not terminated with `endif`".to_owned()));
         }
+        Layout { symbols, addresses, enabled, binaries, reserved }
     }
 
-    fn compile_mov_regpair(pair: &RegisterPair, b: &MovArg) -> Option<(Vec<u8>, String)> {
-        match b {
-            MovArg::Constant(c) => {
-                Some((Self::prepend_to_addr(0x01 + pair.left_table_x_off(), *c), format!("lxi {}, {:04X}", pair.name(), c)))
-            },
-            MovArg::Register(_) => {
-                eprintln!("Loading register into register pair not supported");
-                None
-            },
-            MovArg::RegisterPair(p) => {
-                if pair == &RegisterPair::SP && p == &RegisterPair::HL {
-                    Some((vec![0xF9], format!("sphl")))
-                } else if (pair == &RegisterPair::DE && p == &RegisterPair::HL) || (pair == &RegisterPair::HL && p == &RegisterPair::DE) {
-                    Some((vec![0xEB], format!("xchg")))
-                } else {
-                    eprintln!("Moving values between common register pairs is not supported");
-                    None
+    /// Updates the open conditional blocks with the directive
+    fn conditional(statement: &Statement, ctx: &Context, conditionals: &mut Vec<Conditional>, source: &Source, diagnostics: &mut Diagnostics) {
+        match &statement.kind {
+            StatementKind::If(_) | StatementKind::Ifdef(_) | StatementKind::Ifndef(_) => {
+                let outer = conditionals.last().is_none_or(|c| c.active);
+                conditionals.push(Conditional { span: statement.span, outer, active: false, taken: false, else_span: None });
+            }
+            kind => {
+                let name = match kind {
+                    StatementKind::Elif(_) => "elif",
+                    StatementKind::Else => "else",
+                    _ => "endif",
+                };
+                let Some(current) = conditionals.last_mut() else {
+                    diagnostics.push(source.error(statement.span, format!("`{}` without `if`", name)));
+                    return;
+                };
+                match (kind, current.else_span) {
+                    (StatementKind::Endif, _) => {
+                        conditionals.pop();
+                        return;
+                    }
+                    (_, Some(previous)) => {
+                        diagnostics.push(source.error(statement.span, format!("`{}` after `else`", name))
+                            .with_note(source.note(previous, "`else` is here".to_owned())));
+                        return;
+                    }
+                    (StatementKind::Else, None) => current.else_span = Some(statement.span),
+                    _ => {}
                 }
-            },
-            MovArg::MemoryDirect(addr) => {
-                if pair != &RegisterPair::HL {
-                    eprintln!("Loading value in pair {} is not supported", pair.name());
-                    None
-                } else {
-                    Some((Self::prepend_to_addr(0x2A, *addr), format!("lhld {:04X}", addr)))
+            }
+        }
+        // a branch is assembled when no previous one was and its condition holds, conditions of other branches
+        // are not evaluated, so they may use undefined symbols
+        let current = conditionals.last_mut().unwrap();
+        current.active = match &statement.kind {
+            _ if !current.outer || current.taken => false,
+            StatementKind::If(expr) | StatementKind::Elif(expr) => match ctx.eval(expr) {
+                Ok(value) => value != 0,
+                Err(e) => {
+                    diagnostics.push(e.explain_undefined("conditions can only use symbols defined above them").diagnostic(source, statement));
+                    false
                 }
             },
-            MovArg::MemoryIndirect(p) => {
-                if pair == &RegisterPair::HL && p == &RegisterPair::SP {
-                    Some((vec![0xE3], format!("xthl")))
-                } else {
-                    eprintln!("Indirect memory access is supported only for HL from SP");
-                    None
-                }
-            }
+            StatementKind::Ifdef(name) => ctx.symbols.get(name).is_some(),
+            StatementKind::Ifndef(name) => ctx.symbols.get(name).is_none(),
+            _ => true,
+        };
+        current.taken |= current.active;
+    }
+
+    /// Size of `ds` or `align` at the address of the context, the value can only use symbols defined above it
+    fn reserved_size(kind: &StatementKind, ctx: &Context) -> Result<u16, StatementError> {
+        match kind {
+            StatementKind::Ds(n, _) => ctx.word(n).map_err(|e| e.explain_undefined("ds can only use symbols defined above it")),
+            StatementKind::Align(n) => match ctx.word(n) {
+                Ok(0) => Err(StatementError::at(n.span, "Alignment must be positive".to_owned())),
+                Ok(n) => Ok(((n as u32 - ctx.address as u32 % n as u32) % n as u32) as u16),
+                Err(e) => Err(e.explain_undefined("align can only use symbols defined above it")),
+            },
+            _ => Ok(kind.size()),
         }
     }
 
-    fn compile_mov_register(a_reg: &Register, b: &MovArg) -> Option<(Vec<u8>, String)> {
-        match b {
-            MovArg::Register(b_reg) => {
-                if *a_reg == Register::M && *b_reg == Register::M {
-                    eprintln!("mov m, m not supported");
-                    None
-                } else {
-                    Some((vec![a_reg.mov_base_off() + b_reg.code_off()], format!("mov {}, {}", a_reg.name(), b_reg.name())))
-                }
+    /// Reads the part of the file of `incbin`, offset and length can only use symbols defined above it
+    fn incbin(&self, name: &str, range: &Option<(Expr, Option<Expr>)>, ctx: &Context, from: &str) -> Result<Vec<u8>, StatementError> {
+        let path = preprocessor::resolve(name, from, &self.include_paths)
+            .ok_or_else(|| format!("Cannot find included file `{}`", name))?;
+        let data = std::fs::read(&path).map_err(|e| format!("Cannot read `{}`: {}", path.display(), e))?;
+        let (offset, length) = match range {
+            None => (0, None),
+            Some((offset, length)) => {
+                let offset = ctx.word(offset).map_err(|e| e.explain_undefined("incbin can only use symbols defined above it"))?;
+                let length = length.as_ref().map(|x| ctx.word(x)).transpose()
+                    .map_err(|e| e.explain_undefined("incbin can only use symbols defined above it"))?;
+                (offset as usize, length.map(usize::from))
             }
-            MovArg::Constant(c) => {
-                if *c >= 256u16 {
-                    eprintln!("Cannot put 16-bit constant into 8-bit register");
-                    None
-                } else {
-                    let cmd = if a_reg.left_table_is_down() { 0x0E } else { 0x06 } + a_reg.left_table_x_off();
-                    Some((vec![cmd, *c as u8], format!("mvi {}, {:02X}", a_reg.name(), c)))
+        };
+        let end = length.map(|x| offset + x).unwrap_or(data.len()).max(offset);
+        if end > data.len() {
+            return Err(format!("File `{}` is {} bytes long, but bytes up to {} are included", name, data.len(), end).into());
+        }
+        Ok(data[offset..end].to_vec())
+    }
+
+    fn define_symbol(symbols: &mut SymbolTable, name: &str, kind: SymbolKind, value: u16, statement: &Statement, source: &Source, diagnostics: &mut Diagnostics) {
+        if let Err(previous) = symbols.define(name, kind, value, statement.span) {
+            diagnostics.push(source.error(statement.span, format!("Symbol {} is defined multiple times", name))
+                .with_note(source.note(previous, "previous definition is here".to_owned())));
+        }
+    }
+
+    /// Encodes the statement, returning its code and the instruction in Intel syntax
+    fn compile_statement(statement: &Statement, ctx: &Context, dialect: Dialect) -> Result<(Vec<u8>, String), StatementError> {
+        let (mnemonic, operands, immediate) = match &statement.kind {
+            StatementKind::Lset(_) | StatementKind::Equ(_) | StatementKind::Set(_) | StatementKind::Org(_) | StatementKind::Entry(_) | StatementKind::Empty => return Ok((vec![], String::new())),
+            StatementKind::Db(items, terminator) => return Self::db(items, *terminator, ctx),
+            StatementKind::Dw(items, order) => {
+                let mut code = Vec::with_capacity(items.len() * 2);
+                let mut shown = Vec::with_capacity(items.len());
+                for x in items {
+                    code.extend(order.unwrap_or(dialect.word_order()).bytes(ctx.word(x)?));
+                    shown.push(ctx.show_word(x)?);
                 }
+                let mnemonic = match order {
+                    None => "dw",
+                    Some(ByteOrder::Little) => "dwle",
+                    Some(ByteOrder::Big) => "dwbe",
+                };
+                return Ok((code, format!("{} {}", mnemonic, shown.join(", "))));
             }
-            MovArg::RegisterPair(_) => {
-                eprintln!("Cannot move register pair into register");
-                None
+            kind => Self::instruction(kind, ctx)?,
+        };
+        let opcode = opcodes::find(mnemonic, &operands)
+            .ok_or_else(|| format!("Instruction {} is not supported", mnemonic))?;
+        let (value, shown) = match (operands.last(), immediate) {
+            (Some(Operand::Byte), Some(e)) => (ctx.byte(e)? as u16, ctx.show_byte(e)?),
+            (Some(Operand::Port), Some(e)) => {
+                let port = ctx.byte(e)?;
+                (port as u16, Context::show(e, port.to_string()))
             }
-            MovArg::MemoryDirect(mem) => {
-                if *a_reg != Register::A {
-                    eprintln!("Cannot load register {} from memory", a_reg.name());
-                    None
-                } else {
-                    Some((Self::prepend_to_addr(0x3A, *mem), format!("lda {:04X}", mem)))
+            (Some(Operand::Word), Some(e)) => (ctx.word(e)?, ctx.show_word(e)?),
+            _ => (0, String::new()),
+        };
+        Ok((opcodes::encode(opcode, value), opcode.format(&shown)))
+    }
+
+    /// Encodes the items of `db`, strings are shown as written and other items as bytes
+    fn db(items: &[Data], terminator: Terminator, ctx: &Context) -> Result<(Vec<u8>, String), StatementError> {
+        let mut code = Vec::new();
+        let mut shown = Vec::with_capacity(items.len());
+        for x in items {
+            match x {
+                Data::Expr(e) => {
+                    code.push(ctx.byte(e)?);
+                    shown.push(ctx.show_byte(e)?);
                 }
-            }
-            MovArg::MemoryIndirect(mem) => {
-                if *a_reg != Register::A {
-                    eprintln!("Cannot load register {} from indirect memory", a_reg.name());
-                    None
-                } else {
-                    match mem {
-                        RegisterPair::BC => Some((vec![0x0A], "ldax b".to_owned())),
-                        RegisterPair::DE => Some((vec![0x1A], "ldax d".to_owned())),
-                        RegisterPair::HL | RegisterPair::SP => {
-                            eprintln!("Cannot load register {} from indirect memory at {}", a_reg.name(), mem.name());
-                            None
-                        }
+                Data::Text(text) => {
+                    for c in text.chars() {
+                        let byte = u8::try_from(c).map_err(|_| format!("Character `{}` does not fit into a byte, use \\x escapes", c))?;
+                        code.push(byte);
                     }
+                    shown.push(x.to_string());
                 }
             }
         }
+        code.extend(terminator.byte());
+        Ok((code, format!("{} {}", terminator.mnemonic(), shown.join(", "))))
+    }
+
+    /// Code of `ds` and `align` taking `size` bytes, which is found by the layout pass
+    fn reserve(statement: &Statement, size: u16, ctx: &Context) -> Result<(Vec<u8>, String), StatementError> {
+        match &statement.kind {
+            StatementKind::Ds(_, Some(fill)) => Ok((vec![ctx.byte(fill)?; size as usize], format!("ds {:04X}, {}", size, ctx.show_byte(fill)?))),
+            StatementKind::Align(n) => Ok((vec![], format!("align {}", ctx.show_word(n)?))),
+            _ => Ok((vec![], format!("ds {:04X}", size))),
+        }
+    }
+
+    /// Intel mnemonic and operands of the statement, with the expression of the immediate operand
+    fn instruction<'a>(kind: &'a StatementKind, ctx: &Context) -> Result<(&'static str, Vec<Operand>, Option<&'a Expr>), StatementError> {
+        Ok(match kind {
+            StatementKind::Cmc => ("cmc", vec![], None),
+            StatementKind::Nop => ("nop", vec![], None),
+            StatementKind::Ral => ("ral", vec![], None),
+            StatementKind::Rar => ("rar", vec![], None),
+            StatementKind::Rlc => ("rlc", vec![], None),
+            StatementKind::Rrc => ("rrc", vec![], None),
+            StatementKind::Stc => ("stc", vec![], None),
+            StatementKind::Daa => ("daa", vec![], None),
+            StatementKind::Hlt => ("hlt", vec![], None),
+            StatementKind::Cli => ("di", vec![], None),
+            StatementKind::Sti => ("ei", vec![], None),
+            StatementKind::Pchl => ("pchl", vec![], None),
+            StatementKind::In(port) => ("in", vec![Operand::Port], Some(port)),
+            StatementKind::Out(port) => ("out", vec![Operand::Port], Some(port)),
+            StatementKind::Jmp(label, typ) => (typ.name(), vec![Operand::Word], Some(label)),
+            StatementKind::Ret(typ) => (typ.name(), vec![], None),
+            StatementKind::Rst(code) => match ctx.byte(code)? {
+                n if n % 8 == 0 && n <= 56 => ("rst", vec![Operand::Rst(n / 8)], None),
+                n => return Err(StatementError::at(code.span, format!("RST code {} not supported", n))),
+            },
+            StatementKind::Push(RegisterPair::SP) => return Err("Cannot push SP onto stack".to_owned().into()),
+            StatementKind::Pop(RegisterPair::SP) => return Err("Cannot pop SP from stack".to_owned().into()),
+            StatementKind::Push(pair) => ("push", vec![Operand::Pair(*pair)], None),
+            StatementKind::Pop(pair) => ("pop", vec![Operand::Pair(*pair)], None),
+            StatementKind::Pushpsw => ("push", vec![Operand::Psw], None),
+            StatementKind::Poppsw => ("pop", vec![Operand::Psw], None),
+            StatementKind::Arif(reg, typ) => (typ.name(), vec![Operand::Reg(*reg)], None),
+            StatementKind::Arifn(c, typ) => (typ.const_name(), vec![Operand::Byte], Some(c)),
+            StatementKind::Dad(pair) => ("dad", vec![Operand::Pair(*pair)], None),
+            StatementKind::Incp(pair) => ("inx", vec![Operand::Pair(*pair)], None),
+            StatementKind::Dcrp(pair) => ("dcx", vec![Operand::Pair(*pair)], None),
+            StatementKind::Inc(reg) => ("inr", vec![Operand::Reg(*reg)], None),
+            StatementKind::Dcr(reg) => ("dcr", vec![Operand::Reg(*reg)], None),
+            StatementKind::Neg(Register::A) => ("cma", vec![], None),
+            StatementKind::Neg(Register::C) => ("cmc", vec![], None),
+            StatementKind::Neg(reg) => return Err(format!("Neg is not supported for register {}", reg.name()).into()),
+            StatementKind::Mov(a, b) => return Ok(Self::mov(a, b)?),
+            StatementKind::Lset(_) | StatementKind::Equ(_) | StatementKind::Set(_) | StatementKind::Org(_) | StatementKind::Entry(_)
+            | StatementKind::Empty | StatementKind::Db(_, _) | StatementKind::Dw(_, _) | StatementKind::Incbin(_, _)
+            | StatementKind::Ds(_, _) | StatementKind::Align(_)
+            | StatementKind::If(_) | StatementKind::Elif(_) | StatementKind::Else | StatementKind::Endif
+            | StatementKind::Ifdef(_) | StatementKind::Ifndef(_) => unreachable!("not an instruction"),
+        })
+    }
+
+    /// Intel instruction for every supported combination of `mov` arguments
+    fn mov<'a>(a: &'a MovArg, b: &'a MovArg) -> Result<(&'static str, Vec<Operand>, Option<&'a Expr>), String> {
+        Ok(match (a, b) {
+            (MovArg::Constant(_), _) => return Err("Cannot move into constant".to_owned()),
+            (MovArg::Register(Register::M), MovArg::Register(Register::M)) => return Err("mov m, m not supported".to_owned()),
+            (MovArg::Register(x), MovArg::Register(y)) => ("mov", vec![Operand::Reg(*x), Operand::Reg(*y)], None),
+            (MovArg::Register(x), MovArg::Constant(c)) => ("mvi", vec![Operand::Reg(*x), Operand::Byte], Some(c)),
+            (MovArg::Register(_), MovArg::RegisterPair(_)) => return Err("Cannot move register pair into register".to_owned()),
+            (MovArg::Register(x), MovArg::MemoryDirect(_)) if *x != Register::A => return Err(format!("Cannot load register {} from memory", x.name())),
+            (MovArg::Register(_), MovArg::MemoryDirect(mem)) => ("lda", vec![Operand::Word], Some(mem)),
+            (MovArg::Register(x), MovArg::MemoryIndirect(_)) if *x != Register::A => {
+                return Err(format!("Cannot load register {} from indirect memory", x.name()));
+            }
+            (MovArg::Register(_), MovArg::MemoryIndirect(pair @ (RegisterPair::BC | RegisterPair::DE))) => ("ldax", vec![Operand::Pair(*pair)], None),
+            (MovArg::Register(x), MovArg::MemoryIndirect(pair)) => return Err(format!("Cannot load register {} from indirect memory at {}", x.name(), pair.name())),
+            (MovArg::RegisterPair(pair), MovArg::Constant(c)) => ("lxi", vec![Operand::Pair(*pair), Operand::Word], Some(c)),
+            (MovArg::RegisterPair(_), MovArg::Register(_)) => return Err("Loading register into register pair not supported".to_owned()),
+            (MovArg::RegisterPair(RegisterPair::SP), MovArg::RegisterPair(RegisterPair::HL)) => ("sphl", vec![], None),
+            (MovArg::RegisterPair(RegisterPair::DE), MovArg::RegisterPair(RegisterPair::HL))
+            | (MovArg::RegisterPair(RegisterPair::HL), MovArg::RegisterPair(RegisterPair::DE)) => ("xchg", vec![], None),
+            (MovArg::RegisterPair(_), MovArg::RegisterPair(_)) => return Err("Moving values between common register pairs is not supported".to_owned()),
+            (MovArg::RegisterPair(RegisterPair::HL), MovArg::MemoryDirect(mem)) => ("lhld", vec![Operand::Word], Some(mem)),
+            (MovArg::RegisterPair(pair), MovArg::MemoryDirect(_)) => return Err(format!("Loading value in pair {} is not supported", pair.name())),
+            (MovArg::RegisterPair(RegisterPair::HL), MovArg::MemoryIndirect(RegisterPair::SP))
+            | (MovArg::MemoryIndirect(RegisterPair::SP), MovArg::RegisterPair(RegisterPair::HL)) => ("xthl", vec![], None),
+            (MovArg::RegisterPair(_), MovArg::MemoryIndirect(_)) | (MovArg::MemoryIndirect(_), MovArg::RegisterPair(_)) => {
+                return Err("Indirect memory access is supported only for HL from SP".to_owned());
+            }
+            (MovArg::MemoryIndirect(_), MovArg::Register(x)) if *x != Register::A => {
+                return Err(format!("Cannot store register {} into indirect memory", x.name()));
+            }
+            (MovArg::MemoryIndirect(pair @ (RegisterPair::BC | RegisterPair::DE)), MovArg::Register(_)) => ("stax", vec![Operand::Pair(*pair)], None),
+            (MovArg::MemoryIndirect(pair), MovArg::Register(x)) => return Err(format!("Cannot load register {} from indirect memory at {}", x.name(), pair.name())),
+            (MovArg::MemoryIndirect(_), _) => return Err("Indirect moving into memory is supported only for registers".to_owned()),
+            (MovArg::MemoryDirect(_), MovArg::Register(x)) if *x != Register::A => return Err(format!("Cannot store register {} into memory", x.name())),
+            (MovArg::MemoryDirect(mem), MovArg::Register(_)) => ("sta", vec![Operand::Word], Some(mem)),
+            (MovArg::MemoryDirect(mem), MovArg::RegisterPair(RegisterPair::HL)) => ("shld", vec![Operand::Word], Some(mem)),
+            (MovArg::MemoryDirect(_), MovArg::RegisterPair(pair)) => return Err(format!("Storing value from pair {} is not supported", pair.name())),
+            (MovArg::MemoryDirect(_), _) => return Err("Cannot load something in direct memory other than registers".to_owned()),
+        })
     }
 }
\ No newline at end of file
@@ -0,0 +1,346 @@
+use std::fmt::{Display, Formatter};
+
+/// Byte range in the source text
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    // additional location attached to another diagnostic
+    Note,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+/// Assembler source file, used to turn spans into line/column locations
+#[derive(Clone)]
+pub struct Source {
+    pub name: String,
+    /// Text that is parsed, spans refer to it
+    pub text: String,
+    /// Set when `text` is the result of preprocessing
+    expansion: Option<Expansion>,
+}
+
+/// File read while preprocessing, the first one is the main file
+#[derive(Clone, Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvocationKind {
+    Macro(String),
+    Include,
+}
+
+/// Macro invocation or `include` a line was expanded from
+#[derive(Clone, Debug)]
+pub struct Invocation {
+    pub kind: InvocationKind,
+    /// Index of the file containing the invoking line
+    pub file: usize,
+    /// Offset of the invoking line in that file
+    pub offset: usize,
+}
+
+/// Where a line of the expanded text comes from
+#[derive(Clone, Debug)]
+pub struct LineOrigin {
+    /// Index of the file containing the line
+    pub file: usize,
+    /// Offset of the line in that file
+    pub offset: usize,
+    /// Macro invocations and includes the line was expanded from, innermost first
+    pub invocations: Vec<Invocation>,
+}
+
+#[derive(Clone)]
+struct Expansion {
+    files: Vec<SourceFile>,
+    /// Origin of every line of the expanded text
+    lines: Vec<LineOrigin>,
+}
+
+/// Returns 1-based line and column of the byte offset together with the text of that line
+fn locate(text: &str, offset: usize) -> (usize, usize, &str) {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = text[offset..].find('\n').map(|i| offset + i).unwrap_or(text.len());
+    let line = text[..line_start].matches('\n').count() + 1;
+    let column = text[line_start..offset].chars().count() + 1;
+    (line, column, text[line_start..line_end].trim_end_matches('\r'))
+}
+
+impl Source {
+    pub fn new(name: String, text: String) -> Source {
+        Source { name, text, expansion: None }
+    }
+
+    /// Source whose `text` was produced from `files` by preprocessing, `lines` maps every line of `text` back
+    pub fn expanded(files: Vec<SourceFile>, text: String, lines: Vec<LineOrigin>) -> Source {
+        Source { name: files[0].name.clone(), text, expansion: Some(Expansion { files, lines }) }
+    }
+
+    /// Files the text was produced from, the first one is the main file
+    pub fn files(&self) -> Vec<SourceFile> {
+        match &self.expansion {
+            Some(expansion) => expansion.files.clone(),
+            None => vec![SourceFile { name: self.name.clone(), text: self.text.clone() }],
+        }
+    }
+
+    /// Name and text of the file with the index
+    fn file(&self, file: usize) -> (&str, &str) {
+        match &self.expansion {
+            Some(expansion) => (&expansion.files[file].name, &expansion.files[file].text),
+            None => (&self.name, &self.text),
+        }
+    }
+
+    /// Text of the main file as written
+    pub fn original(&self) -> &str {
+        self.file(0).1
+    }
+
+    /// Origin of the line of `text` with the 0-based index
+    pub fn line_origin(&self, line: usize) -> LineOrigin {
+        match &self.expansion {
+            Some(expansion) => expansion.lines[line].clone(),
+            None => {
+                let offset = self.text.split('\n').take(line).map(|x| x.len() + 1).sum();
+                LineOrigin { file: 0, offset, invocations: Vec::new() }
+            }
+        }
+    }
+
+    /// Origin of the line of `text` containing the byte offset
+    fn origin(&self, offset: usize) -> Option<&LineOrigin> {
+        let expansion = self.expansion.as_ref()?;
+        let offset = offset.min(self.text.len());
+        expansion.lines.get(self.text[..offset].matches('\n').count())
+    }
+
+    /// File and offset in it corresponding to the offset in `text`
+    fn original_offset(&self, offset: usize) -> (usize, usize) {
+        match self.origin(offset) {
+            Some(origin) => {
+                // columns are kept, as far as the original line is long enough
+                let (_, column, _) = locate(&self.text, offset);
+                let (_, _, line) = locate(self.file(origin.file).1, origin.offset);
+                (origin.file, origin.offset + line.char_indices().nth(column - 1).map(|(i, _)| i).unwrap_or(line.len()))
+            }
+            None => (0, offset),
+        }
+    }
+
+    /// Name of the file containing the byte offset
+    pub fn file_name(&self, offset: usize) -> &str {
+        self.file(self.original_offset(offset).0).0
+    }
+
+    /// 1-based number of the line containing the byte offset, in the file it comes from
+    pub fn line(&self, offset: usize) -> usize {
+        let (file, offset) = self.original_offset(offset);
+        locate(self.file(file).1, offset).0
+    }
+
+    /// 1-based line of the main file the byte offset was expanded from by a macro invocation or `include`
+    pub fn invocation_line(&self, offset: usize) -> Option<usize> {
+        let invocation = self.origin(offset)?.invocations.last()?;
+        Some(locate(self.file(invocation.file).1, invocation.offset).0)
+    }
+
+    /// File and 1-based line of the outermost macro invocation the byte offset was expanded from
+    pub fn macro_invocation(&self, offset: usize) -> Option<(&str, usize)> {
+        let invocation = self.origin(offset)?.invocations.iter().rev().find(|x| matches!(x.kind, InvocationKind::Macro(_)))?;
+        let (name, text) = self.file(invocation.file);
+        Some((name, locate(text, invocation.offset).0))
+    }
+
+    /// Text of the line containing the byte offset, after preprocessing
+    pub fn line_text(&self, offset: usize) -> &str {
+        locate(&self.text, offset).2
+    }
+
+    fn diagnostic_at(&self, severity: Severity, span: Span, file: usize, offset: usize, message: String) -> Diagnostic {
+        let (name, text) = self.file(file);
+        let (line, column, source_line) = locate(text, offset);
+        Diagnostic {
+            severity,
+            message,
+            file: name.to_owned(),
+            line,
+            column,
+            span,
+            source_line: source_line.to_owned(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// Diagnostic covering a whole line, with a note for every invocation the line was expanded from
+    pub fn line_diagnostic(&self, severity: Severity, origin: &LineOrigin, message: String) -> Diagnostic {
+        let text = locate(self.file(origin.file).1, origin.offset).2;
+        let start = origin.offset + text.len() - text.trim_start().len();
+        let mut diagnostic = self.diagnostic_at(severity, Span::new(start, start + text.trim().len()), origin.file, start, message);
+        diagnostic.notes = self.invocation_notes(&origin.invocations);
+        diagnostic
+    }
+
+    /// Notes pointing at every invocation, innermost first
+    pub fn invocation_notes(&self, invocations: &[Invocation]) -> Vec<Diagnostic> {
+        invocations.iter()
+            .map(|x| {
+                let message = match &x.kind {
+                    InvocationKind::Macro(name) => format!("in this expansion of macro `{}`", name),
+                    InvocationKind::Include => "in the file included from here".to_owned(),
+                };
+                let origin = LineOrigin { file: x.file, offset: x.offset, invocations: Vec::new() };
+                self.line_diagnostic(Severity::Note, &origin, message)
+            })
+            .collect()
+    }
+
+    /// Diagnostic at the span, with a note for every invocation the span was expanded from
+    pub fn diagnostic(&self, severity: Severity, span: Span, message: String) -> Diagnostic {
+        let (file, offset) = self.original_offset(span.start);
+        let mut diagnostic = self.diagnostic_at(severity, span, file, offset, message);
+        if let Some(origin) = self.origin(span.start) {
+            diagnostic.notes = self.invocation_notes(&origin.invocations);
+        }
+        diagnostic
+    }
+
+    pub fn error(&self, span: Span, message: String) -> Diagnostic {
+        self.diagnostic(Severity::Error, span, message)
+    }
+
+    pub fn warning(&self, span: Span, message: String) -> Diagnostic {
+        self.diagnostic(Severity::Warning, span, message)
+    }
+
+    pub fn note(&self, span: Span, message: String) -> Diagnostic {
+        self.diagnostic(Severity::Note, span, message)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+    pub source_line: String,
+    pub notes: Vec<Diagnostic>,
+}
+
+impl Diagnostic {
+    pub fn with_note(mut self, note: Diagnostic) -> Diagnostic {
+        self.notes.push(note);
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let line_no = self.line.to_string();
+        let gutter = " ".repeat(line_no.len());
+        let prefix: String = self.source_line.chars().take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let rest = self.source_line.chars().count().saturating_sub(self.column - 1);
+        let width = (self.span.end.saturating_sub(self.span.start)).clamp(1, rest.max(1));
+        writeln!(f, "{}: {}", self.severity.name(), self.message)?;
+        writeln!(f, "{}--> {}:{}:{}", gutter, self.file, self.line, self.column)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line_no, self.source_line)?;
+        writeln!(f, "{} | {}{}", gutter, prefix, "^".repeat(width))?;
+        for x in &self.notes {
+            write!(f, "{}", x)?;
+        }
+        Ok(())
+    }
+}
+
+/// All diagnostics collected while processing a file
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics { items: Vec::new() }
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.items.push(diagnostic);
+    }
+
+    /// Orders diagnostics by their position in the source
+    pub fn sort(&mut self) {
+        self.items.sort_by_key(|d| d.span.start);
+    }
+
+    pub fn retain(&mut self, f: impl FnMut(&Diagnostic) -> bool) {
+        self.items.retain(f);
+    }
+
+    /// Turns warnings into errors
+    pub fn deny_warnings(&mut self) {
+        for x in self.items.iter_mut().filter(|d| d.severity == Severity::Warning) {
+            x.severity = Severity::Error;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.items.iter().filter(|d| d.severity == Severity::Error).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for x in &self.items {
+            writeln!(f, "{}", x)?;
+        }
+        match self.error_count() {
+            0 => Ok(()),
+            1 => writeln!(f, "error: aborting due to previous error"),
+            n => writeln!(f, "error: aborting due to {} previous errors", n),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+/// Continuous block of memory filled by the program
+#[derive(Clone, Debug)]
+pub struct Segment {
+    pub origin: u16,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    /// Address right after the last byte of the segment
+    pub fn end(&self) -> u32 {
+        self.origin as u32 + self.data.len() as u32
+    }
+}
+
+/// Sparse memory image, segments are ordered by address and do not overlap
+#[derive(Clone, Debug, Default)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+}
+
+impl Image {
+    pub fn new() -> Image {
+        Image { segments: Vec::new() }
+    }
+
+    /// Writes bytes at the address, continuing the last written segment if possible
+    pub fn write(&mut self, address: u16, bytes: &[u8]) {
+        match self.segments.last_mut() {
+            Some(last) if last.end() == address as u32 => last.data.extend_from_slice(bytes),
+            _ => self.segments.push(Segment { origin: address, data: bytes.to_vec() }),
+        }
+    }
+
+    /// Orders segments by address and merges adjacent ones
+    pub fn normalize(&mut self) {
+        self.segments.retain(|s| !s.data.is_empty());
+        self.segments.sort_by_key(|s| s.origin);
+        let mut merged: Vec<Segment> = Vec::new();
+        for x in self.segments.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.end() == x.origin as u32 => last.data.extend_from_slice(&x.data),
+                _ => merged.push(x),
+            }
+        }
+        self.segments = merged;
+    }
+
+    /// Lowest used address
+    pub fn start(&self) -> Option<u16> {
+        self.segments.first().map(|s| s.origin)
+    }
+
+    /// Address right after the highest used byte
+    pub fn end(&self) -> Option<u32> {
+        self.segments.iter().map(Segment::end).max()
+    }
+
+    /// Whether some bytes are outside of the range from `start` to `end` (exclusive)
+    pub fn exceeds(&self, start: u32, end: u32) -> bool {
+        self.segments.iter().any(|s| (s.origin as u32) < start || s.end() > end)
+    }
+
+    /// Memory from `start` to `end` (exclusive), unused bytes are filled with `fill`
+    pub fn slice(&self, start: u32, end: u32, fill: u8) -> Vec<u8> {
+        let mut data = vec![fill; end.saturating_sub(start) as usize];
+        for x in &self.segments {
+            let from = (x.origin as u32).max(start);
+            let to = x.end().min(end);
+            if from < to {
+                let src = (from - x.origin as u32) as usize;
+                data[(from - start) as usize..(to - start) as usize].copy_from_slice(&x.data[src..src + (to - from) as usize]);
+            }
+        }
+        data
+    }
+
+    /// Byte at every address of the memory, `None` for unused ones
+    fn memory(&self) -> Vec<Option<u8>> {
+        let mut memory = vec![None; 0x10000];
+        for x in &self.segments {
+            for (i, b) in x.data.iter().enumerate() {
+                memory[x.origin as usize + i] = Some(*b);
+            }
+        }
+        memory
+    }
+
+    /// Addresses where the images differ, with the bytes of both images
+    pub fn diff(&self, other: &Image) -> Vec<(u16, Option<u8>, Option<u8>)> {
+        self.memory().into_iter().zip(other.memory())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(addr, (a, b))| (addr as u16, a, b))
+            .collect()
+    }
+}
@@ -0,0 +1,31 @@
+#[macro_use] extern crate lalrpop_util;
+
+pub(crate) mod ast;
+pub(crate) mod compiler;
+pub(crate) mod diagnostic;
+pub(crate) mod image;
+pub(crate) mod opcodes;
+pub mod output;
+pub(crate) mod parser;
+pub(crate) mod preprocessor;
+pub(crate) mod symbols;
+pub(crate) mod target;
+lalrpop_mod!(#[allow(clippy::all)] grammar);
+
+pub use crate::ast::File;
+pub use crate::compiler::{CompiledStatement, Compiler, CompilerResult, Dialect, Row, Warnings};
+pub use crate::diagnostic::{Diagnostic, Diagnostics, Severity, Source, Span};
+pub use crate::image::{Image, Segment};
+pub use crate::opcodes::{decode, Opcode, OPCODES};
+pub use crate::symbols::{Symbol, SymbolKind};
+pub use crate::target::Target;
+
+/// Preprocesses and parses the text, diagnostics refer to it as `<input>`, included files are searched in the current directory
+pub fn parse(text: &str) -> Result<File, Diagnostics> {
+    parser::parse(&preprocessor::expand(&Source::new("<input>".to_owned(), text.to_owned()), &[])?)
+}
+
+/// Compiles the text with the default options
+pub fn assemble(text: &str) -> Result<CompilerResult, Diagnostics> {
+    Compiler::new().assemble(text)
+}
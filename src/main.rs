@@ -1,57 +1,450 @@
-#[macro_use] extern crate lalrpop_util;
-
 use std::error::Error;
 use std::fs::OpenOptions;
 use std::io::{Read, Write};
-use std::path::Path;
-use clap::Parser;
-use crate::compiler::Compiler;
-
-mod ast;
-mod compiler;
-lalrpop_mod!(pub grammar);
+use std::path::{Path, PathBuf};
+use clap::{ArgEnum, Parser};
+use kr580compiler::{output, Compiler, CompilerResult, Dialect, Source, Target, Warnings};
+use kr580compiler::output::bin::Layout;
+use kr580compiler::output::rom::Rom;
+use kr580compiler::output::table::Quoting;
 
 #[derive(Parser, Debug)]
 #[clap(version = "1.0", author = "Aleksei A. <alesharik4@gmail.com>")]
 struct Opts {
     input: String,
+    /// Directory searched for `include` and `incbin` files not found next to the file using them, can be repeated
+    #[clap(short = 'I', long = "include-path", multiple_occurrences = true, number_of_values = 1)]
+    include_paths: Vec<PathBuf>,
+    /// Symbol for conditional assembly as NAME=value or NAME, which means NAME=1, can be repeated
+    #[clap(short = 'D', long = "define", parse(try_from_str = parse_define), multiple_occurrences = true, number_of_values = 1)]
+    defines: Vec<(String, u16)>,
+    /// Write the table of instructions into <input>_table.<format>
     #[clap(short, long)]
     table: bool,
+    /// Format of the instruction table
+    #[clap(long, arg_enum, default_value = "csv")]
+    table_format: TableFormatOpt,
+    /// Path of the instruction table, implies --table
+    #[clap(long)]
+    table_output: Option<String>,
+    /// Delimiter of CSV table fields
+    #[clap(long, default_value = ";")]
+    table_delimiter: char,
+    /// Which CSV table fields are put in quotes
+    #[clap(long, arg_enum, default_value = "minimal")]
+    table_quoting: QuotingOpt,
+    /// Write labels and constants into a file, see --symbols-format
+    #[clap(short, long)]
+    symbols: bool,
+    /// Format of the symbol file: <input>_symbols.csv, <input>.sym, <input>.noi or <input>_symbols.json
+    #[clap(long, arg_enum, default_value = "csv")]
+    symbols_format: SymbolsFormatOpt,
+    /// Write the sheet for entering the program byte by byte on the stand into <input>_keying.txt
+    #[clap(short, long)]
+    keying: bool,
+    /// Lines on a page of the keying sheet
+    #[clap(long, default_value = "60")]
+    page_length: usize,
+    /// Number of bytes between checksums on the keying sheet, 0 to write only the total
+    #[clap(long, default_value = "16")]
+    checksum_every: usize,
+    /// Write the listing with source lines, addresses, code and cycles into <input>.lst
+    #[clap(short, long)]
+    listing: bool,
+    /// Maximum number of code bytes on a listing line, the rest goes to continuation lines
+    #[clap(long, default_value = "4")]
+    listing_bytes: usize,
+    /// Machine the program is written for, sets the default origin, output format and predefined symbols
+    #[clap(long, arg_enum, default_value = "stand")]
+    target: TargetOpt,
+    /// Address of the program start, can be changed in the source with `org`.
+    /// 0x8200 for the stand and 0x0100 for CP/M by default
+    #[clap(long, parse(try_from_str = parse_number))]
+    origin: Option<u16>,
+    /// Part of the memory written into the .bin file
+    #[clap(long, arg_enum, default_value = "padded")]
+    layout: LayoutOpt,
+    /// Address range for `--layout window`, e.g. 0x8200:0x83FF (end is inclusive)
+    #[clap(long, parse(try_from_str = parse_window))]
+    window: Option<(u16, u16)>,
+    /// Value of unused bytes in the .bin file
+    #[clap(long, parse(try_from_str = parse_byte), default_value = "0")]
+    fill: u8,
+    /// Format of the compiled program, bin for the stand and com for CP/M by default
+    #[clap(short, long, arg_enum)]
+    format: Option<FormatOpt>,
+    /// Maximum number of data bytes in one Intel HEX or S-record, from 1 to 255 or to 252 for S-records
+    #[clap(long, parse(try_from_str = parse_record_length), default_value = "16")]
+    hex_record_length: u8,
+    /// Bits in a word of .mem, .mif and .coe files: 8, 16, 24 ... 64
+    #[clap(long, parse(try_from_str = parse_width), default_value = "8")]
+    rom_width: u32,
+    /// Number of words in .mem, .mif and .coe files, by default enough to hold the program
+    #[clap(long)]
+    rom_depth: Option<u32>,
+    /// Address of the first word in .mem, .mif and .coe files, by default the start of the program
+    #[clap(long, parse(try_from_str = parse_number))]
+    rom_base: Option<u16>,
+    /// Bits per second of the .wav tape recording
+    #[clap(long, parse(try_from_str = parse_rate), default_value = "1200")]
+    baud: u32,
+    /// Samples per second of the .wav tape recording
+    #[clap(long, parse(try_from_str = parse_rate), default_value = "44100")]
+    sample_rate: u32,
+    /// What is done with warnings
+    #[clap(long, arg_enum, default_value = "warn")]
+    warnings: WarningsOpt,
+    /// Source language variant, `legacy` keeps the big-endian `dw` of older versions
+    #[clap(long, arg_enum, default_value = "kr580")]
+    dialect: DialectOpt,
+    /// Compare the compiled program with a .hex, .rk, .rkm or .wav file
+    #[clap(long)]
+    compare: Option<String>,
+}
+
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum LayoutOpt {
+    /// From address 0 to the end of the program
+    Padded,
+    /// From the origin to the last used address
+    Raw,
+    /// Whole 64 KB memory
+    Full,
+    /// Range set by --window
+    Window,
+}
+
+fn parse_byte(s: &str) -> Result<u8, String> {
+    u8::try_from(parse_number(s)?).map_err(|_| format!("{}: value does not fit into a byte", s))
 }
 
-fn build_offset(off: usize) -> Vec<u8> {
-    let mut v = Vec::<u8>::with_capacity(off);
-    for _ in 0..off {
-        v.push(0);
+fn parse_record_length(s: &str) -> Result<u8, String> {
+    match parse_byte(s)? {
+        0 => Err(format!("{}: record length must be positive", s)),
+        n => Ok(n),
     }
-    v
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let opts: Opts = Opts::parse();
-    let mut content = String::new();
-    let filename = Path::new(&opts.input).file_stem().unwrap().to_os_string().to_str().unwrap().to_owned();
-    std::fs::File::open(opts.input)?.read_to_string(&mut content)?;
-    let tokens = grammar::FileParser::new().parse(&content).unwrap();
-    let result = Compiler::new().compile(&tokens);
-    if opts.table {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(format!("{}_table.csv", filename))?;
-        file.write_all("ADDRES;CODE;LABEL;ASM\n".as_bytes())?;
-        for x in &result.table {
-            file.write_all(format!("{}\n", x).as_bytes())?;
-        }
-        file.flush()?;
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum DialectOpt {
+    /// `dw` stores words low byte first, as the processor reads them
+    Kr580,
+    /// `dw` stores words high byte first, as older versions did
+    Legacy,
+}
+
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum WarningsOpt {
+    /// Do not show warnings
+    Allow,
+    Warn,
+    /// Treat warnings as errors
+    Deny,
+}
+
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum TableFormatOpt {
+    Csv,
+    /// All row fields including the source line
+    Json,
+    Markdown,
+    Html,
+}
+
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum QuotingOpt {
+    /// Fields with the delimiter, quotes or line breaks
+    Minimal,
+    Always,
+    Never,
+}
+
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum SymbolsFormatOpt {
+    /// Semicolon-separated name, kind and value
+    Csv,
+    /// Name and value per line
+    Sym,
+    /// NoICE command file
+    Noice,
+    /// JSON array
+    Json,
+}
+
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum TargetOpt {
+    /// KR580 training stand
+    Stand,
+    /// CP/M 2.2
+    Cpm,
+}
+
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum FormatOpt {
+    /// Raw binary, see --layout
+    Bin,
+    /// CP/M program, memory from 0x0100 to the end of the program
+    Com,
+    /// Intel HEX
+    Hex,
+    /// Motorola S-record
+    Srec,
+    /// Verilog $readmemh
+    Mem,
+    /// Altera Memory Initialization File
+    Mif,
+    /// Xilinx coefficient file
+    Coe,
+    /// Radio-86RK tape file
+    Rk,
+    /// Mikrosha tape file
+    Rkm,
+    /// Radio-86RK tape recording
+    Wav,
+}
+
+fn parse_width(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(w) if w % 8 == 0 && (8..=64).contains(&w) => Ok(w),
+        _ => Err(format!("{}: width must be a multiple of 8 from 8 to 64", s)),
     }
+}
+
+fn write_file(path: &str, data: &[u8]) -> std::io::Result<()> {
     let mut file = OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)
-        .open(format!("{}.bin", filename))?;
-    file.write_all(&build_offset(0x8200))?;
-    file.write_all(&result.data)?;
+        .open(path)?;
+    file.write_all(data)?;
+    file.flush()
+}
+
+/// Compares the compiled program with a previously written file and reports the differences
+/// Tape files have no entry point and store gaps filled with `fill`, so the program is compared in the same form
+fn compare(result: &CompilerResult, path: &str, fill: u8) -> Result<bool, Box<dyn Error>> {
+    let mut content = Vec::new();
+    std::fs::File::open(path)?.read_to_end(&mut content)?;
+    let tape = || output::rk::build(result.image(), fill, false).and_then(|data| output::rk::read(&data, false));
+    let (ours, image, entry) = match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("hex") => {
+            let (image, entry) = output::ihex::read(&String::from_utf8_lossy(&content)).map_err(|e| format!("{}: {}", path, e))?;
+            (result.image().clone(), image, entry)
+        }
+        Some("rk") => (tape()?, output::rk::read(&content, false).map_err(|e| format!("{}: {}", path, e))?, result.entry()),
+        Some("rkm") => (tape()?, output::rk::read(&content, true).map_err(|e| format!("{}: {}", path, e))?, result.entry()),
+        Some("wav") => (tape()?, output::wav::read(&content).map_err(|e| format!("{}: {}", path, e))?, result.entry()),
+        _ => return Err(format!("{}: cannot compare with this file type", path).into()),
+    };
+    let diff = ours.diff(&image);
+    let show = |b: Option<u8>| b.map(|b| format!("{:02X}", b)).unwrap_or_else(|| "--".to_owned());
+    for (addr, ours, theirs) in diff.iter().take(16) {
+        eprintln!("{:04X}: {} != {}", addr, show(*ours), show(*theirs));
+    }
+    if diff.len() > 16 {
+        eprintln!("... and {} more", diff.len() - 16);
+    }
+    if entry != result.entry() {
+        eprintln!("entry point differs: {:04X?} != {:04X?}", result.entry(), entry);
+    }
+    if diff.is_empty() && entry == result.entry() {
+        eprintln!("{} matches the compiled program", path);
+        Ok(true)
+    } else {
+        eprintln!("{} differs from the compiled program in {} bytes", path, diff.len());
+        Ok(false)
+    }
+}
+
+fn parse_rate(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(rate) if rate > 0 => Ok(rate),
+        _ => Err(format!("{}: expected a positive number", s)),
+    }
+}
+
+fn parse_window(s: &str) -> Result<(u16, u16), String> {
+    let (start, end) = s.split_once(':').ok_or_else(|| format!("{}: expected START:END", s))?;
+    let (start, end) = (parse_number(start)?, parse_number(end)?);
+    if start > end {
+        return Err(format!("{}: start is after end", s));
+    }
+    Ok((start, end))
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary number
+fn parse_number(s: &str) -> Result<u16, String> {
+    let result = if let Some(d) = s.strip_prefix("0x") {
+        u16::from_str_radix(d, 16)
+    } else if let Some(d) = s.strip_prefix("0b") {
+        u16::from_str_radix(d, 2)
+    } else {
+        s.parse()
+    };
+    result.map_err(|e| format!("{}: {}", s, e))
+}
+
+fn parse_define(s: &str) -> Result<(String, u16), String> {
+    let (name, value) = match s.split_once('=') {
+        Some((name, value)) => (name, parse_number(value)?),
+        None => (s, 1),
+    };
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("{} is not a valid symbol name", name));
+    }
+    Ok((name.to_owned(), value))
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opts: Opts = Opts::parse();
+    let mut content = String::new();
+    let layout = match (opts.layout, opts.window) {
+        (LayoutOpt::Padded, _) => Layout::Padded,
+        (LayoutOpt::Raw, _) => Layout::Raw,
+        (LayoutOpt::Full, _) => Layout::Full,
+        (LayoutOpt::Window, Some((start, end))) => Layout::Window(start, end),
+        (LayoutOpt::Window, None) => {
+            eprintln!("error: --layout window requires --window");
+            std::process::exit(2);
+        }
+    };
+    let filename = Path::new(&opts.input).file_stem().unwrap().to_os_string().to_str().unwrap().to_owned();
+    std::fs::File::open(&opts.input)?.read_to_string(&mut content)?;
+    let target = match opts.target {
+        TargetOpt::Stand => Target::Stand,
+        TargetOpt::Cpm => Target::Cpm,
+    };
+    let format = opts.format.unwrap_or(match target {
+        Target::Stand => FormatOpt::Bin,
+        Target::Cpm => FormatOpt::Com,
+    });
+    if matches!(format, FormatOpt::Srec) && opts.hex_record_length > output::srec::MAX_RECORD_LENGTH {
+        eprintln!("error: --hex-record-length must be at most {} for S-records", output::srec::MAX_RECORD_LENGTH);
+        std::process::exit(2);
+    }
+    let warnings = match opts.warnings {
+        WarningsOpt::Allow => Warnings::Allow,
+        WarningsOpt::Warn => Warnings::Warn,
+        WarningsOpt::Deny => Warnings::Deny,
+    };
+    let dialect = match opts.dialect {
+        DialectOpt::Kr580 => Dialect::Kr580,
+        DialectOpt::Legacy => Dialect::Legacy,
+    };
+    let compiler = opts.include_paths.iter()
+        .fold(Compiler::new().target(target).warnings(warnings).dialect(dialect), |compiler, path| compiler.include_path(path));
+    let compiler = match opts.origin {
+        Some(origin) => compiler.origin(origin),
+        None => compiler,
+    };
+    let compiler = opts.defines.iter().fold(compiler, |compiler, (name, value)| compiler.define(name, *value));
+    let (source, tokens, result) = match compiler.assemble_source(&Source::new(opts.input, content)) {
+        Ok(x) => x,
+        Err(diagnostics) => {
+            eprint!("{}", diagnostics);
+            std::process::exit(1);
+        }
+    };
+    eprint!("{}", result.diagnostics());
+    if opts.table || opts.table_output.is_some() {
+        let quoting = match opts.table_quoting {
+            QuotingOpt::Minimal => Quoting::Minimal,
+            QuotingOpt::Always => Quoting::Always,
+            QuotingOpt::Never => Quoting::Never,
+        };
+        let (table, extension) = match opts.table_format {
+            TableFormatOpt::Csv => (output::table::csv(result.rows(), opts.table_delimiter, quoting), "csv"),
+            TableFormatOpt::Json => (output::table::json(result.rows()), "json"),
+            TableFormatOpt::Markdown => (output::table::markdown(result.rows()), "md"),
+            TableFormatOpt::Html => (output::table::html(result.rows(), &source.name), "html"),
+        };
+        let path = opts.table_output.clone().unwrap_or_else(|| format!("{}_table.{}", filename, extension));
+        write_file(&path, table.as_bytes())?;
+    }
+    if opts.symbols {
+        let (symbols, path) = match opts.symbols_format {
+            SymbolsFormatOpt::Csv => (output::symbols::csv(result.symbols()), format!("{}_symbols.csv", filename)),
+            SymbolsFormatOpt::Sym => (output::symbols::sym(result.symbols()), format!("{}.sym", filename)),
+            SymbolsFormatOpt::Noice => (output::symbols::noice(result.symbols()), format!("{}.noi", filename)),
+            SymbolsFormatOpt::Json => (output::symbols::json(result.symbols()), format!("{}_symbols.json", filename)),
+        };
+        write_file(&path, symbols.as_bytes())?;
+    }
+    if opts.keying {
+        let sheet = output::keying::write(result.rows(), &source.name, opts.page_length, opts.checksum_every);
+        write_file(&format!("{}_keying.txt", filename), sheet.as_bytes())?;
+    }
+    if opts.listing {
+        let listing = output::listing::write(&source, &tokens, &result, opts.listing_bytes);
+        write_file(&format!("{}.lst", filename), listing.as_bytes())?;
+    }
+    let matches = match &opts.compare {
+        Some(path) => compare(&result, path, opts.fill)?,
+        None => true,
+    };
+    match format {
+        FormatOpt::Bin => {
+            if output::bin::is_truncated(result.image(), layout, result.origin()) {
+                eprintln!("warning: part of the program is outside of the written address range");
+            }
+            write_file(&format!("{}.bin", filename), &output::bin::build(result.image(), layout, opts.fill, result.origin()))?;
+        }
+        FormatOpt::Com => {
+            let layout = Layout::From(Target::Cpm.origin());
+            if output::bin::is_truncated(result.image(), layout, result.origin()) {
+                eprintln!("warning: part of the program is below 0x0100 and is not written into the .com file");
+            }
+            write_file(&format!("{}.com", filename), &output::bin::build(result.image(), layout, opts.fill, result.origin()))?;
+        }
+        FormatOpt::Hex => {
+            let hex = output::ihex::write(result.image(), result.entry(), opts.hex_record_length);
+            write_file(&format!("{}.hex", filename), hex.as_bytes())?;
+        }
+        FormatOpt::Srec => {
+            let srec = output::srec::write(result.image(), result.entry(), opts.hex_record_length, &filename);
+            write_file(&format!("{}.srec", filename), srec.as_bytes())?;
+        }
+        FormatOpt::Rk | FormatOpt::Rkm => {
+            let mikrosha = matches!(format, FormatOpt::Rkm);
+            match output::rk::build(result.image(), opts.fill, mikrosha) {
+                Ok(data) => write_file(&format!("{}.{}", filename, if mikrosha { "rkm" } else { "rk" }), &data)?,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        FormatOpt::Wav => match output::wav::tape(result.image(), opts.fill) {
+            Ok(tape) => write_file(&format!("{}.wav", filename), &output::wav::encode(&tape, opts.baud, opts.sample_rate))?,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        FormatOpt::Mem | FormatOpt::Mif | FormatOpt::Coe => {
+            let rom = Rom { width: opts.rom_width, depth: opts.rom_depth, base: opts.rom_base };
+            let (text, extension) = match format {
+                FormatOpt::Mem => (output::rom::mem(&rom, result.image(), opts.fill), "mem"),
+                FormatOpt::Mif => (output::rom::mif(&rom, result.image(), opts.fill), "mif"),
+                _ => (output::rom::coe(&rom, result.image(), opts.fill), "coe"),
+            };
+            let text = match text {
+                Ok(text) => text,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if rom.is_truncated(result.image()) {
+                eprintln!("warning: part of the program is outside of the ROM");
+            }
+            write_file(&format!("{}.{}", filename, extension), text.as_bytes())?;
+        }
+    }
+    if !matches {
+        std::process::exit(1);
+    }
     Ok(())
 }
@@ -0,0 +1,366 @@
+use crate::ast::{Register, RegisterPair};
+use Operand::*;
+use Register::*;
+use RegisterPair::*;
+
+/// Operand of an instruction in Intel syntax
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Operand {
+    /// Register encoded in the opcode
+    Reg(Register),
+    /// Register pair encoded in the opcode
+    Pair(RegisterPair),
+    /// A and flags for push/pop
+    Psw,
+    /// Interrupt vector number encoded in the opcode
+    Rst(u8),
+    /// Immediate byte after the opcode
+    Byte,
+    /// Immediate byte with a port number
+    Port,
+    /// Immediate little-endian word after the opcode
+    Word,
+}
+
+/// Description of an opcode of the KR580 (Intel 8080)
+#[derive(Copy, Clone, Debug)]
+pub struct Opcode {
+    pub code: u8,
+    /// Intel mnemonic
+    pub mnemonic: &'static str,
+    pub operands: &'static [Operand],
+    /// Length of the instruction with its immediate operand
+    pub length: u8,
+    /// Clock cycles, for conditional calls and returns when the condition is false
+    pub cycles: u8,
+    /// Clock cycles when the condition of a call or return is true
+    pub cycles_taken: u8,
+    /// Flags changed by the instruction: Sign, Zero, Auxiliary carry, Parity, Carry
+    pub flags: &'static str,
+    /// Undocumented opcodes duplicate documented ones and are never produced by the encoder
+    pub documented: bool,
+}
+
+impl Opcode {
+    /// Cycles as shown in listings, e.g. `5/11` for conditional returns
+    pub fn cycles_text(&self) -> String {
+        if self.cycles == self.cycles_taken {
+            self.cycles.to_string()
+        } else {
+            format!("{}/{}", self.cycles, self.cycles_taken)
+        }
+    }
+
+    /// Instruction in Intel syntax, `immediate` is the text of the immediate operand
+    pub fn format(&self, immediate: &str) -> String {
+        let operands: Vec<String> = self.operands.iter()
+            .map(|x| match x {
+                Reg(r) => r.name().to_owned(),
+                Pair(p) => p.name().to_owned(),
+                Psw => "psw".to_owned(),
+                Rst(n) => n.to_string(),
+                Byte | Port | Word => immediate.to_owned(),
+            })
+            .collect();
+        if operands.is_empty() {
+            self.mnemonic.to_owned()
+        } else {
+            format!("{} {}", self.mnemonic, operands.join(", "))
+        }
+    }
+}
+
+const fn op(code: u8, mnemonic: &'static str, operands: &'static [Operand], length: u8, cycles: u8, cycles_taken: u8, flags: &'static str) -> Opcode {
+    Opcode { code, mnemonic, operands, length, cycles, cycles_taken, flags, documented: true }
+}
+
+const fn undocumented(code: u8, mnemonic: &'static str, operands: &'static [Operand], length: u8, cycles: u8, cycles_taken: u8, flags: &'static str) -> Opcode {
+    Opcode { code, mnemonic, operands, length, cycles, cycles_taken, flags, documented: false }
+}
+
+/// All opcodes, indexed by their code
+pub static OPCODES: [Opcode; 256] = [
+    op(0x00, "nop", &[], 1, 4, 4, ""),
+    op(0x01, "lxi", &[Pair(BC), Word], 3, 10, 10, ""),
+    op(0x02, "stax", &[Pair(BC)], 1, 7, 7, ""),
+    op(0x03, "inx", &[Pair(BC)], 1, 5, 5, ""),
+    op(0x04, "inr", &[Reg(B)], 1, 5, 5, "SZAP"),
+    op(0x05, "dcr", &[Reg(B)], 1, 5, 5, "SZAP"),
+    op(0x06, "mvi", &[Reg(B), Byte], 2, 7, 7, ""),
+    op(0x07, "rlc", &[], 1, 4, 4, "C"),
+    undocumented(0x08, "nop", &[], 1, 4, 4, ""),
+    op(0x09, "dad", &[Pair(BC)], 1, 10, 10, "C"),
+    op(0x0A, "ldax", &[Pair(BC)], 1, 7, 7, ""),
+    op(0x0B, "dcx", &[Pair(BC)], 1, 5, 5, ""),
+    op(0x0C, "inr", &[Reg(C)], 1, 5, 5, "SZAP"),
+    op(0x0D, "dcr", &[Reg(C)], 1, 5, 5, "SZAP"),
+    op(0x0E, "mvi", &[Reg(C), Byte], 2, 7, 7, ""),
+    op(0x0F, "rrc", &[], 1, 4, 4, "C"),
+    undocumented(0x10, "nop", &[], 1, 4, 4, ""),
+    op(0x11, "lxi", &[Pair(DE), Word], 3, 10, 10, ""),
+    op(0x12, "stax", &[Pair(DE)], 1, 7, 7, ""),
+    op(0x13, "inx", &[Pair(DE)], 1, 5, 5, ""),
+    op(0x14, "inr", &[Reg(D)], 1, 5, 5, "SZAP"),
+    op(0x15, "dcr", &[Reg(D)], 1, 5, 5, "SZAP"),
+    op(0x16, "mvi", &[Reg(D), Byte], 2, 7, 7, ""),
+    op(0x17, "ral", &[], 1, 4, 4, "C"),
+    undocumented(0x18, "nop", &[], 1, 4, 4, ""),
+    op(0x19, "dad", &[Pair(DE)], 1, 10, 10, "C"),
+    op(0x1A, "ldax", &[Pair(DE)], 1, 7, 7, ""),
+    op(0x1B, "dcx", &[Pair(DE)], 1, 5, 5, ""),
+    op(0x1C, "inr", &[Reg(E)], 1, 5, 5, "SZAP"),
+    op(0x1D, "dcr", &[Reg(E)], 1, 5, 5, "SZAP"),
+    op(0x1E, "mvi", &[Reg(E), Byte], 2, 7, 7, ""),
+    op(0x1F, "rar", &[], 1, 4, 4, "C"),
+    undocumented(0x20, "nop", &[], 1, 4, 4, ""),
+    op(0x21, "lxi", &[Pair(HL), Word], 3, 10, 10, ""),
+    op(0x22, "shld", &[Word], 3, 16, 16, ""),
+    op(0x23, "inx", &[Pair(HL)], 1, 5, 5, ""),
+    op(0x24, "inr", &[Reg(H)], 1, 5, 5, "SZAP"),
+    op(0x25, "dcr", &[Reg(H)], 1, 5, 5, "SZAP"),
+    op(0x26, "mvi", &[Reg(H), Byte], 2, 7, 7, ""),
+    op(0x27, "daa", &[], 1, 4, 4, "SZAPC"),
+    undocumented(0x28, "nop", &[], 1, 4, 4, ""),
+    op(0x29, "dad", &[Pair(HL)], 1, 10, 10, "C"),
+    op(0x2A, "lhld", &[Word], 3, 16, 16, ""),
+    op(0x2B, "dcx", &[Pair(HL)], 1, 5, 5, ""),
+    op(0x2C, "inr", &[Reg(L)], 1, 5, 5, "SZAP"),
+    op(0x2D, "dcr", &[Reg(L)], 1, 5, 5, "SZAP"),
+    op(0x2E, "mvi", &[Reg(L), Byte], 2, 7, 7, ""),
+    op(0x2F, "cma", &[], 1, 4, 4, ""),
+    undocumented(0x30, "nop", &[], 1, 4, 4, ""),
+    op(0x31, "lxi", &[Pair(SP), Word], 3, 10, 10, ""),
+    op(0x32, "sta", &[Word], 3, 13, 13, ""),
+    op(0x33, "inx", &[Pair(SP)], 1, 5, 5, ""),
+    op(0x34, "inr", &[Reg(M)], 1, 10, 10, "SZAP"),
+    op(0x35, "dcr", &[Reg(M)], 1, 10, 10, "SZAP"),
+    op(0x36, "mvi", &[Reg(M), Byte], 2, 10, 10, ""),
+    op(0x37, "stc", &[], 1, 4, 4, "C"),
+    undocumented(0x38, "nop", &[], 1, 4, 4, ""),
+    op(0x39, "dad", &[Pair(SP)], 1, 10, 10, "C"),
+    op(0x3A, "lda", &[Word], 3, 13, 13, ""),
+    op(0x3B, "dcx", &[Pair(SP)], 1, 5, 5, ""),
+    op(0x3C, "inr", &[Reg(A)], 1, 5, 5, "SZAP"),
+    op(0x3D, "dcr", &[Reg(A)], 1, 5, 5, "SZAP"),
+    op(0x3E, "mvi", &[Reg(A), Byte], 2, 7, 7, ""),
+    op(0x3F, "cmc", &[], 1, 4, 4, "C"),
+    op(0x40, "mov", &[Reg(B), Reg(B)], 1, 5, 5, ""),
+    op(0x41, "mov", &[Reg(B), Reg(C)], 1, 5, 5, ""),
+    op(0x42, "mov", &[Reg(B), Reg(D)], 1, 5, 5, ""),
+    op(0x43, "mov", &[Reg(B), Reg(E)], 1, 5, 5, ""),
+    op(0x44, "mov", &[Reg(B), Reg(H)], 1, 5, 5, ""),
+    op(0x45, "mov", &[Reg(B), Reg(L)], 1, 5, 5, ""),
+    op(0x46, "mov", &[Reg(B), Reg(M)], 1, 7, 7, ""),
+    op(0x47, "mov", &[Reg(B), Reg(A)], 1, 5, 5, ""),
+    op(0x48, "mov", &[Reg(C), Reg(B)], 1, 5, 5, ""),
+    op(0x49, "mov", &[Reg(C), Reg(C)], 1, 5, 5, ""),
+    op(0x4A, "mov", &[Reg(C), Reg(D)], 1, 5, 5, ""),
+    op(0x4B, "mov", &[Reg(C), Reg(E)], 1, 5, 5, ""),
+    op(0x4C, "mov", &[Reg(C), Reg(H)], 1, 5, 5, ""),
+    op(0x4D, "mov", &[Reg(C), Reg(L)], 1, 5, 5, ""),
+    op(0x4E, "mov", &[Reg(C), Reg(M)], 1, 7, 7, ""),
+    op(0x4F, "mov", &[Reg(C), Reg(A)], 1, 5, 5, ""),
+    op(0x50, "mov", &[Reg(D), Reg(B)], 1, 5, 5, ""),
+    op(0x51, "mov", &[Reg(D), Reg(C)], 1, 5, 5, ""),
+    op(0x52, "mov", &[Reg(D), Reg(D)], 1, 5, 5, ""),
+    op(0x53, "mov", &[Reg(D), Reg(E)], 1, 5, 5, ""),
+    op(0x54, "mov", &[Reg(D), Reg(H)], 1, 5, 5, ""),
+    op(0x55, "mov", &[Reg(D), Reg(L)], 1, 5, 5, ""),
+    op(0x56, "mov", &[Reg(D), Reg(M)], 1, 7, 7, ""),
+    op(0x57, "mov", &[Reg(D), Reg(A)], 1, 5, 5, ""),
+    op(0x58, "mov", &[Reg(E), Reg(B)], 1, 5, 5, ""),
+    op(0x59, "mov", &[Reg(E), Reg(C)], 1, 5, 5, ""),
+    op(0x5A, "mov", &[Reg(E), Reg(D)], 1, 5, 5, ""),
+    op(0x5B, "mov", &[Reg(E), Reg(E)], 1, 5, 5, ""),
+    op(0x5C, "mov", &[Reg(E), Reg(H)], 1, 5, 5, ""),
+    op(0x5D, "mov", &[Reg(E), Reg(L)], 1, 5, 5, ""),
+    op(0x5E, "mov", &[Reg(E), Reg(M)], 1, 7, 7, ""),
+    op(0x5F, "mov", &[Reg(E), Reg(A)], 1, 5, 5, ""),
+    op(0x60, "mov", &[Reg(H), Reg(B)], 1, 5, 5, ""),
+    op(0x61, "mov", &[Reg(H), Reg(C)], 1, 5, 5, ""),
+    op(0x62, "mov", &[Reg(H), Reg(D)], 1, 5, 5, ""),
+    op(0x63, "mov", &[Reg(H), Reg(E)], 1, 5, 5, ""),
+    op(0x64, "mov", &[Reg(H), Reg(H)], 1, 5, 5, ""),
+    op(0x65, "mov", &[Reg(H), Reg(L)], 1, 5, 5, ""),
+    op(0x66, "mov", &[Reg(H), Reg(M)], 1, 7, 7, ""),
+    op(0x67, "mov", &[Reg(H), Reg(A)], 1, 5, 5, ""),
+    op(0x68, "mov", &[Reg(L), Reg(B)], 1, 5, 5, ""),
+    op(0x69, "mov", &[Reg(L), Reg(C)], 1, 5, 5, ""),
+    op(0x6A, "mov", &[Reg(L), Reg(D)], 1, 5, 5, ""),
+    op(0x6B, "mov", &[Reg(L), Reg(E)], 1, 5, 5, ""),
+    op(0x6C, "mov", &[Reg(L), Reg(H)], 1, 5, 5, ""),
+    op(0x6D, "mov", &[Reg(L), Reg(L)], 1, 5, 5, ""),
+    op(0x6E, "mov", &[Reg(L), Reg(M)], 1, 7, 7, ""),
+    op(0x6F, "mov", &[Reg(L), Reg(A)], 1, 5, 5, ""),
+    op(0x70, "mov", &[Reg(M), Reg(B)], 1, 7, 7, ""),
+    op(0x71, "mov", &[Reg(M), Reg(C)], 1, 7, 7, ""),
+    op(0x72, "mov", &[Reg(M), Reg(D)], 1, 7, 7, ""),
+    op(0x73, "mov", &[Reg(M), Reg(E)], 1, 7, 7, ""),
+    op(0x74, "mov", &[Reg(M), Reg(H)], 1, 7, 7, ""),
+    op(0x75, "mov", &[Reg(M), Reg(L)], 1, 7, 7, ""),
+    op(0x76, "hlt", &[], 1, 7, 7, ""),
+    op(0x77, "mov", &[Reg(M), Reg(A)], 1, 7, 7, ""),
+    op(0x78, "mov", &[Reg(A), Reg(B)], 1, 5, 5, ""),
+    op(0x79, "mov", &[Reg(A), Reg(C)], 1, 5, 5, ""),
+    op(0x7A, "mov", &[Reg(A), Reg(D)], 1, 5, 5, ""),
+    op(0x7B, "mov", &[Reg(A), Reg(E)], 1, 5, 5, ""),
+    op(0x7C, "mov", &[Reg(A), Reg(H)], 1, 5, 5, ""),
+    op(0x7D, "mov", &[Reg(A), Reg(L)], 1, 5, 5, ""),
+    op(0x7E, "mov", &[Reg(A), Reg(M)], 1, 7, 7, ""),
+    op(0x7F, "mov", &[Reg(A), Reg(A)], 1, 5, 5, ""),
+    op(0x80, "add", &[Reg(B)], 1, 4, 4, "SZAPC"),
+    op(0x81, "add", &[Reg(C)], 1, 4, 4, "SZAPC"),
+    op(0x82, "add", &[Reg(D)], 1, 4, 4, "SZAPC"),
+    op(0x83, "add", &[Reg(E)], 1, 4, 4, "SZAPC"),
+    op(0x84, "add", &[Reg(H)], 1, 4, 4, "SZAPC"),
+    op(0x85, "add", &[Reg(L)], 1, 4, 4, "SZAPC"),
+    op(0x86, "add", &[Reg(M)], 1, 7, 7, "SZAPC"),
+    op(0x87, "add", &[Reg(A)], 1, 4, 4, "SZAPC"),
+    op(0x88, "adc", &[Reg(B)], 1, 4, 4, "SZAPC"),
+    op(0x89, "adc", &[Reg(C)], 1, 4, 4, "SZAPC"),
+    op(0x8A, "adc", &[Reg(D)], 1, 4, 4, "SZAPC"),
+    op(0x8B, "adc", &[Reg(E)], 1, 4, 4, "SZAPC"),
+    op(0x8C, "adc", &[Reg(H)], 1, 4, 4, "SZAPC"),
+    op(0x8D, "adc", &[Reg(L)], 1, 4, 4, "SZAPC"),
+    op(0x8E, "adc", &[Reg(M)], 1, 7, 7, "SZAPC"),
+    op(0x8F, "adc", &[Reg(A)], 1, 4, 4, "SZAPC"),
+    op(0x90, "sub", &[Reg(B)], 1, 4, 4, "SZAPC"),
+    op(0x91, "sub", &[Reg(C)], 1, 4, 4, "SZAPC"),
+    op(0x92, "sub", &[Reg(D)], 1, 4, 4, "SZAPC"),
+    op(0x93, "sub", &[Reg(E)], 1, 4, 4, "SZAPC"),
+    op(0x94, "sub", &[Reg(H)], 1, 4, 4, "SZAPC"),
+    op(0x95, "sub", &[Reg(L)], 1, 4, 4, "SZAPC"),
+    op(0x96, "sub", &[Reg(M)], 1, 7, 7, "SZAPC"),
+    op(0x97, "sub", &[Reg(A)], 1, 4, 4, "SZAPC"),
+    op(0x98, "sbb", &[Reg(B)], 1, 4, 4, "SZAPC"),
+    op(0x99, "sbb", &[Reg(C)], 1, 4, 4, "SZAPC"),
+    op(0x9A, "sbb", &[Reg(D)], 1, 4, 4, "SZAPC"),
+    op(0x9B, "sbb", &[Reg(E)], 1, 4, 4, "SZAPC"),
+    op(0x9C, "sbb", &[Reg(H)], 1, 4, 4, "SZAPC"),
+    op(0x9D, "sbb", &[Reg(L)], 1, 4, 4, "SZAPC"),
+    op(0x9E, "sbb", &[Reg(M)], 1, 7, 7, "SZAPC"),
+    op(0x9F, "sbb", &[Reg(A)], 1, 4, 4, "SZAPC"),
+    op(0xA0, "ana", &[Reg(B)], 1, 4, 4, "SZAPC"),
+    op(0xA1, "ana", &[Reg(C)], 1, 4, 4, "SZAPC"),
+    op(0xA2, "ana", &[Reg(D)], 1, 4, 4, "SZAPC"),
+    op(0xA3, "ana", &[Reg(E)], 1, 4, 4, "SZAPC"),
+    op(0xA4, "ana", &[Reg(H)], 1, 4, 4, "SZAPC"),
+    op(0xA5, "ana", &[Reg(L)], 1, 4, 4, "SZAPC"),
+    op(0xA6, "ana", &[Reg(M)], 1, 7, 7, "SZAPC"),
+    op(0xA7, "ana", &[Reg(A)], 1, 4, 4, "SZAPC"),
+    op(0xA8, "xra", &[Reg(B)], 1, 4, 4, "SZAPC"),
+    op(0xA9, "xra", &[Reg(C)], 1, 4, 4, "SZAPC"),
+    op(0xAA, "xra", &[Reg(D)], 1, 4, 4, "SZAPC"),
+    op(0xAB, "xra", &[Reg(E)], 1, 4, 4, "SZAPC"),
+    op(0xAC, "xra", &[Reg(H)], 1, 4, 4, "SZAPC"),
+    op(0xAD, "xra", &[Reg(L)], 1, 4, 4, "SZAPC"),
+    op(0xAE, "xra", &[Reg(M)], 1, 7, 7, "SZAPC"),
+    op(0xAF, "xra", &[Reg(A)], 1, 4, 4, "SZAPC"),
+    op(0xB0, "ora", &[Reg(B)], 1, 4, 4, "SZAPC"),
+    op(0xB1, "ora", &[Reg(C)], 1, 4, 4, "SZAPC"),
+    op(0xB2, "ora", &[Reg(D)], 1, 4, 4, "SZAPC"),
+    op(0xB3, "ora", &[Reg(E)], 1, 4, 4, "SZAPC"),
+    op(0xB4, "ora", &[Reg(H)], 1, 4, 4, "SZAPC"),
+    op(0xB5, "ora", &[Reg(L)], 1, 4, 4, "SZAPC"),
+    op(0xB6, "ora", &[Reg(M)], 1, 7, 7, "SZAPC"),
+    op(0xB7, "ora", &[Reg(A)], 1, 4, 4, "SZAPC"),
+    op(0xB8, "cmp", &[Reg(B)], 1, 4, 4, "SZAPC"),
+    op(0xB9, "cmp", &[Reg(C)], 1, 4, 4, "SZAPC"),
+    op(0xBA, "cmp", &[Reg(D)], 1, 4, 4, "SZAPC"),
+    op(0xBB, "cmp", &[Reg(E)], 1, 4, 4, "SZAPC"),
+    op(0xBC, "cmp", &[Reg(H)], 1, 4, 4, "SZAPC"),
+    op(0xBD, "cmp", &[Reg(L)], 1, 4, 4, "SZAPC"),
+    op(0xBE, "cmp", &[Reg(M)], 1, 7, 7, "SZAPC"),
+    op(0xBF, "cmp", &[Reg(A)], 1, 4, 4, "SZAPC"),
+    op(0xC0, "rnz", &[], 1, 5, 11, ""),
+    op(0xC1, "pop", &[Pair(BC)], 1, 10, 10, ""),
+    op(0xC2, "jnz", &[Word], 3, 10, 10, ""),
+    op(0xC3, "jmp", &[Word], 3, 10, 10, ""),
+    op(0xC4, "cnz", &[Word], 3, 11, 17, ""),
+    op(0xC5, "push", &[Pair(BC)], 1, 11, 11, ""),
+    op(0xC6, "adi", &[Byte], 2, 7, 7, "SZAPC"),
+    op(0xC7, "rst", &[Rst(0)], 1, 11, 11, ""),
+    op(0xC8, "rz", &[], 1, 5, 11, ""),
+    op(0xC9, "ret", &[], 1, 10, 10, ""),
+    op(0xCA, "jz", &[Word], 3, 10, 10, ""),
+    undocumented(0xCB, "jmp", &[Word], 3, 10, 10, ""),
+    op(0xCC, "cz", &[Word], 3, 11, 17, ""),
+    op(0xCD, "call", &[Word], 3, 17, 17, ""),
+    op(0xCE, "aci", &[Byte], 2, 7, 7, "SZAPC"),
+    op(0xCF, "rst", &[Rst(1)], 1, 11, 11, ""),
+    op(0xD0, "rnc", &[], 1, 5, 11, ""),
+    op(0xD1, "pop", &[Pair(DE)], 1, 10, 10, ""),
+    op(0xD2, "jnc", &[Word], 3, 10, 10, ""),
+    op(0xD3, "out", &[Port], 2, 10, 10, ""),
+    op(0xD4, "cnc", &[Word], 3, 11, 17, ""),
+    op(0xD5, "push", &[Pair(DE)], 1, 11, 11, ""),
+    op(0xD6, "sui", &[Byte], 2, 7, 7, "SZAPC"),
+    op(0xD7, "rst", &[Rst(2)], 1, 11, 11, ""),
+    op(0xD8, "rc", &[], 1, 5, 11, ""),
+    undocumented(0xD9, "ret", &[], 1, 10, 10, ""),
+    op(0xDA, "jc", &[Word], 3, 10, 10, ""),
+    op(0xDB, "in", &[Port], 2, 10, 10, ""),
+    op(0xDC, "cc", &[Word], 3, 11, 17, ""),
+    undocumented(0xDD, "call", &[Word], 3, 17, 17, ""),
+    op(0xDE, "sbi", &[Byte], 2, 7, 7, "SZAPC"),
+    op(0xDF, "rst", &[Rst(3)], 1, 11, 11, ""),
+    op(0xE0, "rpo", &[], 1, 5, 11, ""),
+    op(0xE1, "pop", &[Pair(HL)], 1, 10, 10, ""),
+    op(0xE2, "jpo", &[Word], 3, 10, 10, ""),
+    op(0xE3, "xthl", &[], 1, 18, 18, ""),
+    op(0xE4, "cpo", &[Word], 3, 11, 17, ""),
+    op(0xE5, "push", &[Pair(HL)], 1, 11, 11, ""),
+    op(0xE6, "ani", &[Byte], 2, 7, 7, "SZAPC"),
+    op(0xE7, "rst", &[Rst(4)], 1, 11, 11, ""),
+    op(0xE8, "rpe", &[], 1, 5, 11, ""),
+    op(0xE9, "pchl", &[], 1, 5, 5, ""),
+    op(0xEA, "jpe", &[Word], 3, 10, 10, ""),
+    op(0xEB, "xchg", &[], 1, 4, 4, ""),
+    op(0xEC, "cpe", &[Word], 3, 11, 17, ""),
+    undocumented(0xED, "call", &[Word], 3, 17, 17, ""),
+    op(0xEE, "xri", &[Byte], 2, 7, 7, "SZAPC"),
+    op(0xEF, "rst", &[Rst(5)], 1, 11, 11, ""),
+    op(0xF0, "rp", &[], 1, 5, 11, ""),
+    op(0xF1, "pop", &[Psw], 1, 10, 10, "SZAPC"),
+    op(0xF2, "jp", &[Word], 3, 10, 10, ""),
+    op(0xF3, "di", &[], 1, 4, 4, ""),
+    op(0xF4, "cp", &[Word], 3, 11, 17, ""),
+    op(0xF5, "push", &[Psw], 1, 11, 11, ""),
+    op(0xF6, "ori", &[Byte], 2, 7, 7, "SZAPC"),
+    op(0xF7, "rst", &[Rst(6)], 1, 11, 11, ""),
+    op(0xF8, "rm", &[], 1, 5, 11, ""),
+    op(0xF9, "sphl", &[], 1, 5, 5, ""),
+    op(0xFA, "jm", &[Word], 3, 10, 10, ""),
+    op(0xFB, "ei", &[], 1, 4, 4, ""),
+    op(0xFC, "cm", &[Word], 3, 11, 17, ""),
+    undocumented(0xFD, "call", &[Word], 3, 17, 17, ""),
+    op(0xFE, "cpi", &[Byte], 2, 7, 7, "SZAPC"),
+    op(0xFF, "rst", &[Rst(7)], 1, 11, 11, ""),
+];
+
+/// Documented opcode of the instruction with the operands, immediate operands are given only by their kind
+pub fn find(mnemonic: &str, operands: &[Operand]) -> Option<&'static Opcode> {
+    OPCODES.iter().find(|x| x.documented && x.mnemonic == mnemonic && x.operands == operands)
+}
+
+/// Encodes the instruction, `immediate` is the value of the immediate operand if it has one
+pub fn encode(opcode: &Opcode, immediate: u16) -> Vec<u8> {
+    let mut bytes = vec![opcode.code];
+    bytes.extend_from_slice(&immediate.to_le_bytes()[..opcode.length as usize - 1]);
+    bytes
+}
+
+/// Decodes the instruction at the start of `bytes` into Intel syntax and its length.
+/// Returns `None` if the immediate operand is cut off
+pub fn decode(bytes: &[u8]) -> Option<(String, usize)> {
+    let opcode = &OPCODES[*bytes.first()? as usize];
+    let len = opcode.length as usize;
+    let operand = bytes.get(1..len)?;
+    let immediate = match (opcode.operands.last(), operand) {
+        (Some(Port), [port]) => port.to_string(),
+        (_, [b]) => format!("{:02X}", b),
+        (_, [lo, hi]) => format!("{:04X}", u16::from_le_bytes([*lo, *hi])),
+        _ => String::new(),
+    };
+    Some((opcode.format(&immediate), len))
+}
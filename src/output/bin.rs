@@ -0,0 +1,45 @@
+use crate::image::Image;
+
+/// Which part of the memory is written into a raw binary file
+#[derive(Copy, Clone, Debug)]
+pub enum Layout {
+    /// From address 0 to the end of the program, as loaded by the training stand
+    Padded,
+    /// From the origin to the highest used address, so that the file is loaded at the origin.
+    /// Starts lower if code is placed below the origin
+    Raw,
+    /// Whole 64 KB address space
+    Full,
+    /// Addresses from `start` to `end` inclusive
+    Window(u16, u16),
+    /// From the address to the end of the program, as CP/M loads .com files
+    From(u16),
+}
+
+impl Layout {
+    /// Address range written into the file, `end` is exclusive
+    fn range(&self, image: &Image, origin: u16) -> (u32, u32) {
+        let start = image.start().unwrap_or(origin) as u32;
+        let end = image.end().unwrap_or(start);
+        match self {
+            Layout::Padded => (0, end),
+            Layout::Raw => (start.min(origin as u32), end),
+            Layout::Full => (0, 0x10000),
+            Layout::Window(start, end) => (*start as u32, *end as u32 + 1),
+            Layout::From(start) => (*start as u32, end.max(*start as u32)),
+        }
+    }
+}
+
+/// Builds binary file contents, unused memory is filled with `fill`.
+/// `origin` is the address the program is loaded at
+pub fn build(image: &Image, layout: Layout, fill: u8, origin: u16) -> Vec<u8> {
+    let (start, end) = layout.range(image, origin);
+    image.slice(start, end, fill)
+}
+
+/// Whether some bytes of the program are not written with the layout
+pub fn is_truncated(image: &Image, layout: Layout, origin: u16) -> bool {
+    let (start, end) = layout.range(image, origin);
+    image.exceeds(start, end)
+}
@@ -0,0 +1,96 @@
+use crate::image::Image;
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const START_LINEAR_ADDRESS: u8 = 0x05;
+
+fn record(typ: u8, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.push(typ);
+    bytes.extend_from_slice(data);
+    let checksum = bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)).wrapping_neg();
+    bytes.push(checksum);
+    let mut line = String::from(":");
+    for b in bytes {
+        line += &format!("{:02X}", b);
+    }
+    line + "\n"
+}
+
+/// Writes the image as Intel HEX with at most `record_length` data bytes per record, which must be positive.
+/// The entry point is written as a start segment address record
+pub fn write(image: &Image, entry: Option<u16>, record_length: u8) -> String {
+    let mut out = String::new();
+    for segment in &image.segments {
+        let mut address = segment.origin;
+        for chunk in segment.data.chunks(record_length as usize) {
+            out += &record(DATA, address, chunk);
+            address = address.wrapping_add(chunk.len() as u16);
+        }
+    }
+    if let Some(entry) = entry {
+        // CS:IP with zero code segment
+        let mut data = vec![0, 0];
+        data.extend_from_slice(&entry.to_be_bytes());
+        out += &record(START_SEGMENT_ADDRESS, 0, &data);
+    }
+    out += &record(END_OF_FILE, 0, &[]);
+    out
+}
+
+fn parse_record(line: &str) -> Result<(u8, u16, Vec<u8>), String> {
+    let hex = line.strip_prefix(':').ok_or("record does not start with `:`")?;
+    if !hex.is_ascii() {
+        return Err("record contains characters other than hex digits".to_owned());
+    }
+    if hex.len() % 2 != 0 || hex.len() < 10 {
+        return Err("record has invalid length".to_owned());
+    }
+    let bytes = (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("invalid hex digits `{}`", &hex[i..i + 2])))
+        .collect::<Result<Vec<u8>, String>>()?;
+    let len = bytes[0] as usize;
+    if bytes.len() != len + 5 {
+        return Err(format!("record declares {} data bytes, but has {}", len, bytes.len() - 5));
+    }
+    if bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) != 0 {
+        return Err("checksum mismatch".to_owned());
+    }
+    Ok((bytes[3], u16::from_be_bytes([bytes[1], bytes[2]]), bytes[4..4 + len].to_vec()))
+}
+
+/// Reads an Intel HEX file into an image, returning the start address if the file has one
+pub fn read(text: &str) -> Result<(Image, Option<u16>), String> {
+    let mut image = Image::new();
+    let mut entry = None;
+    // upper bits of the address set by extended address records
+    let mut base = 0u32;
+    for (idx, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let (typ, address, data) = parse_record(line.trim()).map_err(|e| format!("line {}: {}", idx + 1, e))?;
+        match typ {
+            DATA => {
+                let address = base + address as u32;
+                if address + data.len() as u32 > 0x10000 {
+                    return Err(format!("line {}: data at {:X} is outside of 64 KB memory", idx + 1, address));
+                }
+                image.write(address as u16, &data);
+            }
+            END_OF_FILE => break,
+            EXTENDED_SEGMENT_ADDRESS if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 4,
+            EXTENDED_LINEAR_ADDRESS if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16,
+            START_SEGMENT_ADDRESS if data.len() == 4 => {
+                let cs = u16::from_be_bytes([data[0], data[1]]) as u32;
+                let ip = u16::from_be_bytes([data[2], data[3]]) as u32;
+                entry = Some(((cs << 4) + ip) as u16);
+            }
+            START_LINEAR_ADDRESS if data.len() == 4 => entry = Some(u16::from_be_bytes([data[2], data[3]])),
+            _ => return Err(format!("line {}: unsupported record type {:02X}", idx + 1, typ)),
+        }
+    }
+    image.normalize();
+    Ok((image, entry))
+}
@@ -0,0 +1,55 @@
+use crate::compiler::Row;
+
+/// Printable sheet for entering the program byte by byte: address, hex and octal byte and the instruction
+/// on its first byte. A checksum line follows every `checksum_every` bytes, pages of `page_length` lines
+/// are separated with form feeds
+pub fn write(rows: &[Row], title: &str, page_length: usize, checksum_every: usize) -> String {
+    let mut rows: Vec<&Row> = rows.iter().collect();
+    rows.sort_by_key(|x| x.address);
+    let mut lines = Vec::new();
+    // sum of the current block and of all bytes so far
+    let (mut block, mut total) = (0u8, 0u16);
+    let mut count = 0;
+    let mut block_start = None;
+    let mut last = 0;
+    for row in rows {
+        for (i, b) in row.bytes.iter().enumerate() {
+            let address = row.address.wrapping_add(i as u16);
+            last = address;
+            block_start.get_or_insert(address);
+            let asm = match (i, &row.label) {
+                (0, Some(label)) => format!("{}: {}", label, row.asm()),
+                (0, None) => row.asm(),
+                _ => String::new(),
+            };
+            lines.push(format!("{:04X}   {:02X}   {:03o}   {}", address, b, b, asm).trim_end().to_owned());
+            block = block.wrapping_add(*b);
+            total = total.wrapping_add(*b as u16);
+            count += 1;
+            if checksum_every > 0 && count % checksum_every == 0 {
+                lines.push(format!("------ {:04X}-{:04X} sum {:02X}, total {:04X}", block_start.take().unwrap(), address, block, total));
+                block = 0;
+            }
+        }
+    }
+    if let Some(start) = block_start {
+        lines.push(format!("------ {:04X}-{:04X} sum {:02X}, total {:04X}", start, last, block, total));
+    }
+
+    let page_length = page_length.max(4);
+    // every page starts with the title and the column names
+    let body = page_length - 3;
+    let pages = lines.len().div_ceil(body).max(1);
+    let mut out = String::new();
+    for page in 0..pages {
+        if page > 0 {
+            out += "\x0C";
+        }
+        out += &format!("{}    page {}/{}\nADDR   HEX  OCT   INSTRUCTION\n\n", title, page + 1, pages);
+        for x in lines.iter().skip(page * body).take(body) {
+            out += x;
+            out += "\n";
+        }
+    }
+    out
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use crate::ast::File;
+use crate::compiler::{CompiledStatement, CompilerResult};
+use crate::diagnostic::Source;
+use crate::opcodes::OPCODES;
+use crate::symbols::SymbolKind;
+
+fn row(line: &str, address: &str, code: &[u8], cycles: &str, source: &str, bytes_per_line: usize) -> String {
+    let hex: Vec<String> = code.iter().map(|b| format!("{:02X}", b)).collect();
+    let row = format!("{:>5}  {:<5}  {:<w$}  {:>6}  {}", line, address, hex.join(" "), cycles, source, w = bytes_per_line * 3 - 1);
+    row.trim_end().to_owned() + "\n"
+}
+
+/// Rows of one statement: its address, code and cycles, long data continues on lines with only an address and bytes
+fn statement(line: &str, statement: &CompiledStatement, text: &str, bytes_per_line: usize) -> String {
+    if let Some(value) = statement.value {
+        return row(line, &format!("={:04X}", value), &[], "", text, bytes_per_line);
+    }
+    let cycles = match statement.code.first() {
+        Some(opcode) if statement.instruction => OPCODES[*opcode as usize].cycles_text(),
+        _ => String::new(),
+    };
+    let mut chunks = statement.code.chunks(bytes_per_line);
+    let first = chunks.next().unwrap_or_default();
+    let mut out = row(line, &format!("{:04X}", statement.address), first, &cycles, text, bytes_per_line);
+    for (i, chunk) in chunks.enumerate() {
+        let address = statement.address.wrapping_add(((i + 1) * bytes_per_line) as u16);
+        out += &row("", &format!("{:04X}", address), chunk, "", "", bytes_per_line);
+    }
+    out
+}
+
+/// Line of a definition or reference. Lines of other files are prefixed with the file name,
+/// lines expanded from a macro are shown as the line invoking it followed by `+`, as in the listing
+fn location(source: &Source, offset: usize) -> String {
+    let (name, line, suffix) = match source.macro_invocation(offset) {
+        Some((name, line)) => (name, line, "+"),
+        None => (source.file_name(offset), source.line(offset), ""),
+    };
+    if name == source.name {
+        format!("{}{}", line, suffix)
+    } else {
+        format!("{}:{}{}", name, line, suffix)
+    }
+}
+
+/// Classic assembler listing: every source line with its address, code and cycles,
+/// followed by the symbol table and the cross-reference of symbols
+pub fn write(source: &Source, file: &File, result: &CompilerResult, bytes_per_line: usize) -> String {
+    let bytes_per_line = bytes_per_line.max(1);
+    // statements expanded from a macro are listed under the line invoking it
+    let mut by_line: HashMap<usize, Vec<&CompiledStatement>> = HashMap::new();
+    for x in result.statements() {
+        let line = source.invocation_line(x.span.start).unwrap_or_else(|| source.line(x.span.start));
+        by_line.entry(line).or_default().push(x);
+    }
+    let mut out = format!("{}\n\n{:>5}  {:<5}  {:<w$}  {:>6}  SOURCE\n", source.name, "LINE", "ADDR", "CODE", "CYCLES",
+                          w = bytes_per_line * 3 - 1);
+    for (idx, text) in source.original().lines().enumerate() {
+        let text = text.trim_end_matches('\r');
+        let line = (idx + 1).to_string();
+        let statements = by_line.get(&(idx + 1)).map(Vec::as_slice).unwrap_or_default();
+        let (expanded, direct): (Vec<&CompiledStatement>, Vec<&CompiledStatement>) = statements.iter()
+            .partition(|x| source.invocation_line(x.span.start).is_some());
+        match direct.first() {
+            Some(x) => out += &statement(&line, x, text, bytes_per_line),
+            None => out += &row(&line, "", &[], "", text, bytes_per_line),
+        }
+        for x in expanded {
+            out += &statement("", x, &format!("+ {}", source.line_text(x.span.start).trim()), bytes_per_line);
+        }
+    }
+
+    // statements of conditional blocks that were not assembled do not refer to anything
+    let mut references: HashMap<&str, Vec<String>> = HashMap::new();
+    for (statement, _) in file.statements.iter().zip(result.enabled()).filter(|(_, enabled)| **enabled) {
+        let line = location(source, statement.span.start);
+        for expr in statement.kind.expressions() {
+            for label in expr.labels() {
+                let lines = references.entry(label.strip_prefix('.').unwrap_or(label)).or_default();
+                if lines.last() != Some(&line) {
+                    lines.push(line.clone());
+                }
+            }
+        }
+    }
+    let width = result.symbols().iter().map(|s| s.name.len()).max().unwrap_or(0).max(6);
+    out += &format!("\nSYMBOL TABLE\n\n{:<w$}  {:<5}  VALUE\n", "NAME", "KIND", w = width);
+    for x in result.symbols() {
+        out += &format!("{:<w$}  {:<5}  {:04X}\n", x.name, x.kind.name(), x.value, w = width);
+    }
+    // predefined symbols have no definition in the source
+    let defined: Vec<String> = result.symbols().iter()
+        .map(|x| if x.kind == SymbolKind::Builtin { String::new() } else { location(source, x.span.start) })
+        .collect();
+    let defined_width = defined.iter().map(String::len).max().unwrap_or(0).max(7);
+    out += &format!("\nCROSS REFERENCE\n\n{:<w$}  {:>d$}  REFERENCES\n", "NAME", "DEFINED", w = width, d = defined_width);
+    for (x, defined) in result.symbols().iter().zip(defined) {
+        let lines = references.get(x.name.as_str()).map(|l| l.join(", ")).unwrap_or_default();
+        let row = format!("{:<w$}  {:>d$}  {}", x.name, defined, lines, w = width, d = defined_width);
+        out += &(row.trim_end().to_owned() + "\n");
+    }
+    out
+}
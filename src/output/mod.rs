@@ -0,0 +1,11 @@
+pub mod bin;
+pub mod ihex;
+pub mod json;
+pub mod keying;
+pub mod listing;
+pub mod rk;
+pub mod rom;
+pub mod srec;
+pub mod symbols;
+pub mod table;
+pub mod wav;
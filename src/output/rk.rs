@@ -0,0 +1,68 @@
+use crate::image::Image;
+
+/// Checksum of a tape file as computed by the Radio-86RK monitor.
+/// Every byte but the last one is added to both halves of the sum, the last one only to the low byte
+pub fn checksum(data: &[u8]) -> u16 {
+    let (last, rest) = match data.split_last() {
+        Some(x) => x,
+        None => return 0,
+    };
+    let sum = rest.iter().fold(0u16, |acc, b| acc.wrapping_add(*b as u16 * 0x101));
+    (sum & 0xFF00) | (sum as u8).wrapping_add(*last) as u16
+}
+
+/// Checksum of a tape file as computed by the Mikrosha monitor: bytes at even offsets
+/// are XORed into the low byte, bytes at odd offsets into the high one
+pub fn checksum_mikrosha(data: &[u8]) -> u16 {
+    let (mut lo, mut hi) = (0u8, 0u8);
+    for pair in data.chunks(2) {
+        lo ^= pair[0];
+        hi ^= pair.get(1).copied().unwrap_or(0);
+    }
+    u16::from_be_bytes([hi, lo])
+}
+
+/// Builds a tape file: start and end (inclusive) address, data, sync byte and checksum.
+/// Gaps between segments are filled with `fill`
+pub fn build(image: &Image, fill: u8, mikrosha: bool) -> Result<Vec<u8>, String> {
+    let (start, end) = match (image.start(), image.end()) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return Err("cannot write an empty program to a tape file".to_owned()),
+    };
+    let data = image.slice(start as u32, end, fill);
+    let checksum = if mikrosha { checksum_mikrosha(&data) } else { checksum(&data) };
+    let mut out = Vec::with_capacity(data.len() + 9);
+    out.extend_from_slice(&start.to_be_bytes());
+    out.extend_from_slice(&((end - 1) as u16).to_be_bytes());
+    out.extend_from_slice(&data);
+    out.extend_from_slice(&[0x00, 0x00, 0xE6]);
+    out.extend_from_slice(&checksum.to_be_bytes());
+    Ok(out)
+}
+
+/// Reads a tape file, checking the Radio-86RK checksum or the Mikrosha one if `mikrosha` is set
+pub fn read(data: &[u8], mikrosha: bool) -> Result<Image, String> {
+    if data.len() < 4 {
+        return Err("tape file is too short".to_owned());
+    }
+    let start = u16::from_be_bytes([data[0], data[1]]);
+    let end = u16::from_be_bytes([data[2], data[3]]);
+    if end < start {
+        return Err(format!("end address {:04X} is before start address {:04X}", end, start));
+    }
+    let len = (end - start) as usize + 1;
+    let body = data.get(4..4 + len).ok_or("tape file is shorter than its header says")?;
+    // the checksum follows the `00 00 E6` sync sequence
+    let trailer = data.get(4 + len..4 + len + 5).ok_or("tape file has no checksum")?;
+    if trailer[..3] != [0x00, 0x00, 0xE6] {
+        return Err("no sync byte before the checksum".to_owned());
+    }
+    let expected = u16::from_be_bytes([trailer[3], trailer[4]]);
+    let actual = if mikrosha { checksum_mikrosha(body) } else { checksum(body) };
+    if actual != expected {
+        return Err(format!("checksum mismatch: {:04X} != {:04X}", actual, expected));
+    }
+    let mut image = Image::new();
+    image.write(start, body);
+    Ok(image)
+}
@@ -0,0 +1,82 @@
+use crate::image::Image;
+
+/// Shape of a ROM initialization file
+#[derive(Copy, Clone, Debug)]
+pub struct Rom {
+    /// Bits in one word, a multiple of 8. Bytes are packed into words little-endian
+    pub width: u32,
+    /// Number of words, by default enough to hold the program
+    pub depth: Option<u32>,
+    /// Memory address of the first word, by default the lowest used address
+    pub base: Option<u16>,
+}
+
+impl Rom {
+    fn word_bytes(&self) -> u32 {
+        (self.width / 8).max(1)
+    }
+
+    /// Address range covered by the ROM, `end` is exclusive. Fails if the ROM does not fit into the 64 KB address space
+    fn range(&self, image: &Image) -> Result<(u32, u32), String> {
+        let base = self.base.or_else(|| image.start()).unwrap_or(0) as u32;
+        let depth = self.depth.unwrap_or_else(|| {
+            let end = image.end().unwrap_or(base).max(base);
+            (end - base).div_ceil(self.word_bytes())
+        });
+        match depth.checked_mul(self.word_bytes()).and_then(|size| size.checked_add(base)) {
+            Some(end) if end <= 0x10000 => Ok((base, end)),
+            _ => Err(format!("ROM of {} words of {} bits at {:04X} does not fit into the 64 KB address space", depth, self.width, base)),
+        }
+    }
+
+    /// Words of the ROM, unused memory is filled with `fill`
+    pub fn words(&self, image: &Image, fill: u8) -> Result<Vec<u64>, String> {
+        let (start, end) = self.range(image)?;
+        Ok(image.slice(start, end, fill)
+            .chunks(self.word_bytes() as usize)
+            .map(|w| w.iter().rev().fold(0u64, |acc, b| acc << 8 | *b as u64))
+            .collect())
+    }
+
+    /// Whether some bytes of the program are outside of the ROM, false if the ROM does not fit into memory
+    pub fn is_truncated(&self, image: &Image) -> bool {
+        self.range(image).is_ok_and(|(start, end)| image.exceeds(start, end))
+    }
+
+    fn digits(&self) -> usize {
+        self.word_bytes() as usize * 2
+    }
+}
+
+/// Verilog `$readmemh` file, one word per line
+pub fn mem(rom: &Rom, image: &Image, fill: u8) -> Result<String, String> {
+    let mut out = String::new();
+    for w in rom.words(image, fill)? {
+        out += &format!("{:01$X}\n", w, rom.digits());
+    }
+    Ok(out)
+}
+
+/// Altera/Intel Memory Initialization File
+pub fn mif(rom: &Rom, image: &Image, fill: u8) -> Result<String, String> {
+    let words = rom.words(image, fill)?;
+    let mut out = format!("WIDTH={};\nDEPTH={};\n\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\n\nCONTENT BEGIN\n", rom.width, words.len());
+    for (addr, w) in words.iter().enumerate() {
+        out += &format!("    {:X} : {:02$X};\n", addr, w, rom.digits());
+    }
+    Ok(out + "END;\n")
+}
+
+/// Xilinx coefficient file for block memory generator
+pub fn coe(rom: &Rom, image: &Image, fill: u8) -> Result<String, String> {
+    let words = rom.words(image, fill)?;
+    let mut out = String::from("memory_initialization_radix=16;\nmemory_initialization_vector=\n");
+    for (i, w) in words.iter().enumerate() {
+        let end = if i + 1 == words.len() { ';' } else { ',' };
+        out += &format!("{:01$X}{2}\n", w, rom.digits(), end);
+    }
+    if words.is_empty() {
+        out += ";\n";
+    }
+    Ok(out)
+}
@@ -0,0 +1,39 @@
+use crate::image::Image;
+
+/// Most data bytes in a record, the byte count also covers the address and the checksum
+pub const MAX_RECORD_LENGTH: u8 = 252;
+
+fn record(typ: char, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8 + 3];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.extend_from_slice(data);
+    let checksum = !bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+    bytes.push(checksum);
+    let mut line = format!("S{}", typ);
+    for b in bytes {
+        line += &format!("{:02X}", b);
+    }
+    line + "\n"
+}
+
+/// Writes the image as Motorola S-records (S19) with at most `record_length` data bytes per record,
+/// from 1 to `MAX_RECORD_LENGTH`.
+/// `name` goes into the header record, cut to `MAX_RECORD_LENGTH` bytes, the entry point into the termination record
+pub fn write(image: &Image, entry: Option<u16>, record_length: u8, name: &str) -> String {
+    let header = &name.as_bytes()[..name.len().min(MAX_RECORD_LENGTH as usize)];
+    let mut out = record('0', 0, header);
+    let mut count = 0u32;
+    for segment in &image.segments {
+        let mut address = segment.origin;
+        for chunk in segment.data.chunks(record_length as usize) {
+            out += &record('1', address, chunk);
+            address = address.wrapping_add(chunk.len() as u16);
+            count += 1;
+        }
+    }
+    if count <= 0xFFFF {
+        out += &record('5', count as u16, &[]);
+    }
+    out += &record('9', entry.unwrap_or(0), &[]);
+    out
+}
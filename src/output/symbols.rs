@@ -0,0 +1,41 @@
+use crate::output::json;
+use crate::symbols::Symbol;
+
+/// Semicolon-separated table with the kind of every symbol
+pub fn csv(symbols: &[Symbol]) -> String {
+    let mut out = String::from("NAME;KIND;VALUE\n");
+    for x in symbols {
+        out += &format!("{};{};{:04X}\n", x.name, x.kind.name(), x.value);
+    }
+    out
+}
+
+/// Plain symbol file, a name and a hex value per line
+pub fn sym(symbols: &[Symbol]) -> String {
+    let mut out = String::new();
+    for x in symbols {
+        out += &format!("{} {:04X}\n", x.name, x.value);
+    }
+    out
+}
+
+/// NoICE debugger command file defining every symbol
+pub fn noice(symbols: &[Symbol]) -> String {
+    let mut out = String::new();
+    for x in symbols {
+        out += &format!("DEF {} {:04X}\n", x.name, x.value);
+    }
+    out
+}
+
+/// JSON array of objects with name, kind and numeric value
+pub fn json(symbols: &[Symbol]) -> String {
+    let items: Vec<String> = symbols.iter()
+        .map(|x| format!("  {{\"name\": {}, \"kind\": {}, \"value\": {}}}", json::string(&x.name), json::string(x.kind.name()), x.value))
+        .collect();
+    if items.is_empty() {
+        "[]\n".to_owned()
+    } else {
+        format!("[\n{}\n]\n", items.join(",\n"))
+    }
+}
@@ -0,0 +1,100 @@
+use crate::compiler::Row;
+use crate::output::json;
+
+/// When CSV fields are put in quotes
+#[derive(Copy, Clone, Debug)]
+pub enum Quoting {
+    /// Only fields containing the delimiter, quotes or line breaks
+    Minimal,
+    Always,
+    /// Never, the delimiter in a field breaks the columns
+    Never,
+}
+
+const HEADER: [&str; 5] = ["ADDRESS", "CODE", "LABEL", "ASM", "COMMENT"];
+
+fn cells(row: &Row) -> [String; 5] {
+    let code: Vec<String> = row.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    [
+        format!("{:04X}", row.address),
+        code.join(" "),
+        row.label.clone().unwrap_or_default(),
+        row.asm(),
+        row.comment.clone().unwrap_or_default(),
+    ]
+}
+
+fn csv_field(text: &str, delimiter: char, quoting: Quoting) -> String {
+    let quote = match quoting {
+        Quoting::Minimal => text.contains(delimiter) || text.contains('"') || text.contains('\n'),
+        Quoting::Always => true,
+        Quoting::Never => false,
+    };
+    if quote {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+pub fn csv(rows: &[Row], delimiter: char, quoting: Quoting) -> String {
+    let line = |cells: &[String]| {
+        let fields: Vec<String> = cells.iter().map(|x| csv_field(x, delimiter, quoting)).collect();
+        fields.join(&delimiter.to_string()) + "\n"
+    };
+    let mut out = line(&HEADER.map(String::from));
+    for x in rows {
+        out += &line(&cells(x));
+    }
+    out
+}
+
+/// JSON array with all fields of the rows, including the source line
+pub fn json(rows: &[Row]) -> String {
+    let optional = |x: &Option<String>| x.as_deref().map(json::string).unwrap_or_else(|| "null".to_owned());
+    let items: Vec<String> = rows.iter()
+        .map(|x| {
+            let bytes: Vec<String> = x.bytes.iter().map(|b| b.to_string()).collect();
+            format!("  {{\"address\": {}, \"bytes\": [{}], \"label\": {}, \"mnemonic\": {}, \"operands\": {}, \"comment\": {}, \"line\": {}, \"source\": {}}}",
+                    x.address, bytes.join(", "), optional(&x.label), json::string(&x.mnemonic), json::string(&x.operands),
+                    optional(&x.comment), x.line, json::string(&x.source))
+        })
+        .collect();
+    if items.is_empty() {
+        "[]\n".to_owned()
+    } else {
+        format!("[\n{}\n]\n", items.join(",\n"))
+    }
+}
+
+pub fn markdown(rows: &[Row]) -> String {
+    let line = |cells: &[String]| {
+        let fields: Vec<String> = cells.iter().map(|x| x.replace('|', "\\|")).collect();
+        format!("| {} |\n", fields.join(" | "))
+    };
+    let mut out = line(&HEADER.map(String::from));
+    out += "|:----:|:-----|:------|:----|:--------|\n";
+    for x in rows {
+        out += &line(&cells(x));
+    }
+    out
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Standalone HTML page with the table
+pub fn html(rows: &[Row], title: &str) -> String {
+    let line = |tag: &str, cells: &[String]| {
+        let fields: Vec<String> = cells.iter().map(|x| format!("<{0}>{1}</{0}>", tag, html_escape(x))).collect();
+        format!("<tr>{}</tr>\n", fields.join(""))
+    };
+    let mut out = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<table>\n",
+                          html_escape(title));
+    out += &line("th", &HEADER.map(String::from));
+    for x in rows {
+        out += &line("td", &cells(x));
+    }
+    out + "</table>\n</body>\n</html>\n"
+}
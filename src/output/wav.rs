@@ -0,0 +1,152 @@
+use crate::image::Image;
+use crate::output::rk;
+
+/// Zero bytes written before the sync byte so the monitor can lock onto the signal
+const PILOT_LENGTH: usize = 256;
+const SYNC: u8 = 0xE6;
+const HIGH: u8 = 0xC0;
+const LOW: u8 = 0x40;
+
+/// Tape recording of the program as written by the Radio-86RK monitor: pilot, sync byte and the tape file
+pub fn tape(image: &Image, fill: u8) -> Result<Vec<u8>, String> {
+    let mut out = vec![0; PILOT_LENGTH];
+    out.push(SYNC);
+    out.extend(rk::build(image, fill, false)?);
+    Ok(out)
+}
+
+/// Renders bytes as 8-bit mono PCM WAV. Bits go MSB first in phase encoding:
+/// the level is inverted in the first half of a bit and equals the bit in the second one
+pub fn encode(data: &[u8], baud: u32, sample_rate: u32) -> Vec<u8> {
+    let mut samples = Vec::new();
+    let mut half = 0u64;
+    let mut push = |level: bool| {
+        half += 1;
+        // at least one sample per half of a bit, even if the baud rate is too high for the sample rate
+        let end = ((half * sample_rate as u64 / (2 * baud as u64)) as usize).max(samples.len() + 1);
+        samples.resize(end, if level { HIGH } else { LOW });
+    };
+    for byte in data {
+        for i in (0..8).rev() {
+            let bit = byte >> i & 1 == 1;
+            push(!bit);
+            push(bit);
+        }
+    }
+    let mut out = Vec::with_capacity(samples.len() + 44);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + samples.len() as u32).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // mono
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes()); // bytes per second
+    out.extend_from_slice(&1u16.to_le_bytes()); // block align
+    out.extend_from_slice(&8u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+    out.extend(samples);
+    out
+}
+
+/// First channel of a PCM WAV file converted to signed 16-bit samples
+fn samples(wav: &[u8]) -> Result<Vec<i16>, String> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        return Err("not a WAV file".to_owned());
+    }
+    let mut format = None;
+    let mut pos = 12;
+    while pos + 8 <= wav.len() {
+        let id = &wav[pos..pos + 4];
+        let size = u32::from_le_bytes([wav[pos + 4], wav[pos + 5], wav[pos + 6], wav[pos + 7]]) as usize;
+        let body = &wav[pos + 8..(pos + 8 + size).min(wav.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let tag = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]) as usize;
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if tag != 1 || channels == 0 || (bits != 8 && bits != 16) {
+                    return Err("only 8 and 16 bit PCM WAV files are supported".to_owned());
+                }
+                format = Some((channels, bits));
+            }
+            b"data" => {
+                let (channels, bits) = format.ok_or("WAV data before format")?;
+                return Ok(if bits == 8 {
+                    body.iter().step_by(channels).map(|s| (*s as i16 - 128) << 8).collect()
+                } else {
+                    body.chunks_exact(2).step_by(channels).map(|s| i16::from_le_bytes([s[0], s[1]])).collect()
+                });
+            }
+            _ => {}
+        }
+        pos += 8 + size + size % 2;
+    }
+    Err("WAV file has no data".to_owned())
+}
+
+/// Decodes bits starting from the bit center at `transitions[first]`.
+/// The next bit center is looked for about one bit period later, transitions between bits are skipped
+fn decode_bits(transitions: &[(usize, bool)], first: usize, half: f64) -> Vec<bool> {
+    let mut bits = vec![transitions[first].1];
+    let mut pos = transitions[first].0 as f64;
+    let mut i = first + 1;
+    while i < transitions.len() {
+        let distance = transitions[i].0 as f64 - pos;
+        if distance < half * 1.5 {
+            i += 1;
+        } else if distance <= half * 2.5 {
+            bits.push(transitions[i].1);
+            pos = transitions[i].0 as f64;
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    bits
+}
+
+/// Bytes after the pilot and the sync byte, trying both bit phases and both signal polarities
+fn find_sync(bits: &[bool]) -> Option<Vec<u8>> {
+    let pattern: Vec<bool> = (0..16).rev().map(|i| (SYNC as u16) >> i & 1 == 1).collect();
+    for invert in [false, true] {
+        let bits: Vec<bool> = bits.iter().map(|b| b ^ invert).collect();
+        if let Some(start) = bits.windows(16).position(|w| w == pattern.as_slice()) {
+            return Some(bits[start + 16..].chunks_exact(8)
+                .map(|byte| byte.iter().fold(0u8, |acc, b| acc << 1 | *b as u8))
+                .collect());
+        }
+    }
+    None
+}
+
+/// Decodes a tape recording into the bytes following the sync byte
+pub fn decode(wav: &[u8]) -> Result<Vec<u8>, String> {
+    let samples = samples(wav)?;
+    let (min, max) = samples.iter().fold((i16::MAX, i16::MIN), |(lo, hi), s| (lo.min(*s), hi.max(*s)));
+    let threshold = (min as i32 + max as i32) / 2;
+    let levels: Vec<bool> = samples.iter().map(|s| *s as i32 > threshold).collect();
+    let transitions: Vec<(usize, bool)> = levels.windows(2).enumerate()
+        .filter(|(_, w)| w[0] != w[1])
+        .map(|(i, w)| (i + 1, w[1]))
+        .collect();
+    if transitions.len() < 32 {
+        return Err("no tape signal found".to_owned());
+    }
+    // the pilot is the most of the early signal, so the typical run is a half of a bit
+    let mut runs: Vec<usize> = transitions.windows(2).take(1024).map(|w| w[1].0 - w[0].0).collect();
+    runs.sort_unstable();
+    let half = runs[runs.len() / 2] as f64;
+    for first in 0..2 {
+        if let Some(bytes) = find_sync(&decode_bits(&transitions, first, half)) {
+            return Ok(bytes);
+        }
+    }
+    Err("sync byte not found".to_owned())
+}
+
+/// Reads the program from a tape recording, checking its checksum
+pub fn read(wav: &[u8]) -> Result<Image, String> {
+    rk::read(&decode(wav)?, false)
+}
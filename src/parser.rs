@@ -0,0 +1,149 @@
+use lalrpop_util::{ErrorRecovery, ParseError};
+use lalrpop_util::lexer::Token;
+use crate::ast::File;
+use crate::diagnostic::{Diagnostic, Diagnostics, Source, Span};
+use crate::grammar;
+
+/// Error found by a grammar action, e.g. a number that does not fit into 16 bits
+#[derive(Debug)]
+pub struct SyntaxError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl SyntaxError {
+    pub fn recovery<T>(start: usize, end: usize, message: String) -> ErrorRecovery<usize, T, SyntaxError> {
+        ErrorRecovery {
+            error: ParseError::User { error: SyntaxError { span: Span::new(start, end), message } },
+            dropped_tokens: vec![],
+        }
+    }
+}
+
+/// Replaces escape sequences of string and character literals: `\n`, `\r`, `\t`, `\0`, `\xHH`
+/// and escaped quotes and backslashes
+pub fn unescape(text: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('0') => out.push('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => out.push(c),
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(b) if digits.len() == 2 => out.push(b as char),
+                    _ => return Err(format!("Invalid escape sequence \\x{}", digits)),
+                }
+            }
+            Some(c) => return Err(format!("Invalid escape sequence \\{}", c)),
+            None => return Err("Unterminated escape sequence".to_owned()),
+        }
+    }
+    Ok(out)
+}
+
+/// Parses the whole source, reporting every syntax error instead of stopping at the first one
+pub fn parse(source: &Source) -> Result<File, Diagnostics> {
+    // every line, including the last one, must be terminated for error recovery to work
+    let mut text = source.text.clone();
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    let mut diagnostics = Diagnostics::new();
+    loop {
+        let mut errors = Vec::new();
+        let result = grammar::FileParser::new().parse(&mut errors, &text);
+        // the lexer cannot recover from unknown characters, so they are reported and blanked out
+        // before the text is parsed again, so that the rest of the file is still checked
+        if let Err(ParseError::InvalidToken { location }) = result {
+            let len = text[location..].chars().next().map(char::len_utf8).unwrap_or(1);
+            diagnostics.push(source.error(Span::new(location, location + len), format!("Unknown character `{}`", &text[location..location + len])));
+            text.replace_range(location..location + len, &" ".repeat(len));
+            continue;
+        }
+        for x in errors {
+            diagnostics.push(convert_error(source, x.error));
+        }
+        diagnostics.sort();
+        return match result {
+            Ok(file) if !diagnostics.has_errors() => Ok(file),
+            Ok(_) => Err(diagnostics),
+            Err(e) => {
+                diagnostics.push(convert_error(source, e));
+                Err(diagnostics)
+            }
+        };
+    }
+}
+
+fn convert_error(source: &Source, error: ParseError<usize, Token<'_>, SyntaxError>) -> Diagnostic {
+    match error {
+        ParseError::InvalidToken { location } => {
+            source.error(Span::new(location, location + 1), "Unrecognized token".to_owned())
+        }
+        ParseError::UnrecognizedEOF { location, expected } => {
+            source.error(Span::new(location, location), format!("Unexpected end of file{}", format_expected(&expected)))
+        }
+        ParseError::UnrecognizedToken { token: (l, token, r), expected } => {
+            source.error(Span::new(l, r), format!("Unexpected {}{}", format_token(token.1), format_expected(&expected)))
+        }
+        ParseError::ExtraToken { token: (l, token, r) } => {
+            source.error(Span::new(l, r), format!("Unexpected {}", format_token(token.1)))
+        }
+        ParseError::User { error } => source.error(error.span, error.message),
+    }
+}
+
+fn format_token(token: &str) -> String {
+    if token == "\n" {
+        "end of line".to_owned()
+    } else {
+        format!("`{}`", token)
+    }
+}
+
+/// Turns terminal names from the grammar into a readable list of alternatives
+fn format_expected(expected: &[String]) -> String {
+    let mut names: Vec<String> = expected.iter()
+        .map(|x| {
+            if x.starts_with("r#") {
+                // regular expression terminals
+                if x.ends_with(":\"#") {
+                    "label definition".to_owned()
+                } else if x.starts_with("r#\"[0-9]") {
+                    "number".to_owned()
+                } else if x.starts_with("r#\"\\\"") {
+                    "string".to_owned()
+                } else if x.starts_with("r#\"'") {
+                    "character".to_owned()
+                } else {
+                    "label".to_owned()
+                }
+            } else {
+                match x.trim_matches('"') {
+                    "\\n" => "end of line".to_owned(),
+                    name => format!("`{}`", name),
+                }
+            }
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    if expected.iter().any(|x| x == "\"nop\"") {
+        ", expected an instruction".to_owned()
+    } else if names.is_empty() {
+        String::new()
+    } else if names.len() == 1 {
+        format!(", expected {}", names[0])
+    } else {
+        format!(", expected one of {}", names.join(", "))
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+use crate::diagnostic::{Diagnostics, Invocation, InvocationKind, LineOrigin, Severity, Source, SourceFile};
+use super::{lines, quoted, resolve, split_label, split_word, Output};
+
+struct Includer<'a> {
+    paths: &'a [PathBuf],
+    files: Vec<SourceFile>,
+    output: Output,
+    /// Errors with the line they refer to, turned into diagnostics once all files are read
+    errors: Vec<(LineOrigin, String)>,
+}
+
+impl Includer<'_> {
+    /// Appends the lines of the file to the output, replacing `include` lines with the included files
+    fn file(&mut self, file: usize, invocations: Vec<Invocation>, stack: &mut Vec<PathBuf>) {
+        let text = self.files[file].text.clone();
+        for line in lines(&text) {
+            let origin = LineOrigin { file, offset: line.offset, invocations: invocations.clone() };
+            let (label, code) = split_label(&line.code);
+            let (word, name) = split_word(code);
+            if word != "include" {
+                self.output.push(line.text.to_owned(), origin);
+                continue;
+            }
+            self.output.push_label(label, origin.clone());
+            let name = match quoted(name) {
+                Some(x) => x,
+                None => {
+                    self.errors.push((origin, "Expected a quoted file name after `include`".to_owned()));
+                    continue;
+                }
+            };
+            let path = match resolve(name, &self.files[file].name, self.paths) {
+                Some(x) => x,
+                None => {
+                    self.errors.push((origin, format!("Cannot find included file `{}`", name)));
+                    continue;
+                }
+            };
+            let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+            if stack.contains(&canonical) {
+                self.errors.push((origin, format!("File `{}` is included recursively", name)));
+                continue;
+            }
+            let text = match std::fs::read_to_string(&path) {
+                Ok(x) => x,
+                Err(e) => {
+                    self.errors.push((origin, format!("Cannot read `{}`: {}", path.display(), e)));
+                    continue;
+                }
+            };
+            self.files.push(SourceFile { name: path.to_string_lossy().into_owned(), text });
+            let mut nested = vec![Invocation { kind: InvocationKind::Include, file, offset: line.offset }];
+            nested.extend(invocations.iter().cloned());
+            stack.push(canonical);
+            self.file(self.files.len() - 1, nested, stack);
+            stack.pop();
+        }
+    }
+}
+
+/// Replaces `include "file"` lines with the contents of the files
+pub fn expand(source: &Source, paths: &[PathBuf]) -> Result<Source, Diagnostics> {
+    let mut includer = Includer { paths, files: source.files(), output: Output::default(), errors: Vec::new() };
+    let mut stack: Vec<PathBuf> = PathBuf::from(&source.name).canonicalize().into_iter().collect();
+    includer.file(0, Vec::new(), &mut stack);
+    if includer.files.len() == 1 && includer.errors.is_empty() {
+        return Ok(source.clone());
+    }
+    let result = includer.output.into_source(includer.files);
+    if includer.errors.is_empty() {
+        return Ok(result);
+    }
+    let mut diagnostics = Diagnostics::new();
+    for (origin, message) in includer.errors {
+        diagnostics.push(result.line_diagnostic(Severity::Error, &origin, message));
+    }
+    Err(diagnostics)
+}
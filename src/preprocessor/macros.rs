@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+use crate::diagnostic::{Diagnostic, Diagnostics, Invocation, InvocationKind, LineOrigin, Source};
+use super::{is_identifier, lines, split_args, split_label, split_word, Line, Output};
+
+/// Invocations nested deeper than this are reported as recursive
+const MAX_DEPTH: usize = 64;
+
+/// Words the grammar reads as registers, instructions and directives, so written without a dot
+/// they never refer to a label
+const KEYWORDS: &[&str] = &[
+    "a", "b", "c", "d", "e", "h", "l", "m", "bc", "de", "hl", "sp", "psw", "pc",
+    "adc", "add", "and", "call", "cc", "cli", "cm", "cmc", "cmp", "cnc", "cnz", "cp", "cpe", "cpo", "cz",
+    "daa", "dad", "dec", "hlt", "in", "inc", "jc", "jm", "jmp", "jnc", "jnz", "jp", "jpe", "jpo", "jz",
+    "mov", "neg", "nop", "or", "out", "pop", "push", "ral", "rar", "rc", "ret", "rlc", "rm", "rnc", "rnz",
+    "rp", "rpe", "rpo", "rrc", "rst", "rz", "sbb", "stc", "sti", "sub", "xor",
+    "align", "db", "dbd", "dbz", "ds", "dw", "dwbe", "dwle", "elif", "else", "endif", "entry", "equ",
+    "hi", "if", "ifdef", "ifndef", "incbin", "lo", "lset", "org", "set",
+];
+
+struct Macro {
+    name: String,
+    params: Vec<String>,
+    /// Index of the `macro` line
+    line: usize,
+    /// Indexes of the body lines
+    body: Vec<usize>,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Replaces whole identifiers outside of quotes and comments: parameters with the arguments
+/// and local labels with their unique names. Labels are renamed where written with a dot
+/// and, unless the word is a keyword, without it
+fn substitute(text: &str, params: &HashMap<&str, String>, labels: &HashMap<&str, String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    let mut quote = None;
+    while let Some(c) = rest.chars().next() {
+        if quote.is_none() && (c == ';' || rest.starts_with("//") || rest.starts_with("/*")) {
+            break;
+        }
+        if quote.is_none() && is_word_char(c) {
+            let end = rest.find(|c: char| !(is_word_char(c) || c == '.')).unwrap_or(rest.len());
+            let word = &rest[..end];
+            // dots inside of words are part of them, so a dot before the word starts a label reference
+            let renamed = if c.is_ascii_digit() {
+                None
+            } else if out.ends_with('.') {
+                labels.get(word).or_else(|| params.get(word))
+            } else {
+                params.get(word).or_else(|| labels.get(word).filter(|_| !KEYWORDS.contains(&word)))
+            };
+            out += renamed.map(String::as_str).unwrap_or(word);
+            rest = &rest[end..];
+            continue;
+        }
+        match quote {
+            Some(_) if c == '\\' => {
+                let len = rest[1..].chars().next().map(|c| c.len_utf8() + 1).unwrap_or(1);
+                out += &rest[..len];
+                rest = &rest[len..];
+                continue;
+            }
+            Some(q) if c == q => quote = None,
+            None if c == '"' || c == '\'' => quote = Some(c),
+            _ => {}
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out + rest
+}
+
+struct Expander<'a> {
+    source: &'a Source,
+    lines: Vec<Line<'a>>,
+    macros: HashMap<String, Macro>,
+    /// Number of expansions so far, makes local labels unique
+    counter: usize,
+    /// Identifiers written in the source, unique names of local labels must not clash with them
+    identifiers: HashSet<&'a str>,
+    /// Comment of the invocation being expanded, it goes to the first line with code
+    comment: Option<String>,
+    output: Output,
+    diagnostics: Diagnostics,
+}
+
+impl Expander<'_> {
+    /// Error at the line expanded by the invocations
+    fn error(&self, line: usize, invocations: &[Invocation], message: String) -> Diagnostic {
+        let mut error = self.source.error(self.lines[line].span(), message);
+        error.notes.extend(self.source.invocation_notes(invocations));
+        error
+    }
+
+    /// Origin of the line of the input expanded by the invocations
+    fn origin(&self, line: usize, invocations: &[Invocation]) -> LineOrigin {
+        let mut origin = self.source.line_origin(line);
+        origin.invocations.extend_from_slice(invocations);
+        origin
+    }
+
+    /// Collects macro definitions, returns for every line whether it belongs to one
+    fn definitions(&mut self) -> Vec<bool> {
+        let mut hidden = vec![false; self.lines.len()];
+        let mut current: Option<Macro> = None;
+        for (idx, hide) in hidden.iter_mut().enumerate() {
+            let (label, code) = split_label(&self.lines[idx].code);
+            let (word, rest) = split_word(code);
+            match word.to_ascii_lowercase().as_str() {
+                "macro" => {
+                    if let Some(x) = &current {
+                        let error = self.source.error(self.lines[idx].span(), "Macro definitions cannot be nested".to_owned())
+                            .with_note(self.source.note(self.lines[x.line].span(), format!("inside of macro `{}`", x.name)));
+                        self.diagnostics.push(error);
+                        *hide = true;
+                        continue;
+                    }
+                    if label.is_some() {
+                        self.diagnostics.push(self.source.error(self.lines[idx].span(), "Macro definition cannot be labeled".to_owned()));
+                    }
+                    let (name, params) = split_word(rest);
+                    let params = split_args(params);
+                    if !is_identifier(name) {
+                        self.diagnostics.push(self.source.error(self.lines[idx].span(), "Expected macro name".to_owned()));
+                    }
+                    for x in &params {
+                        if !is_identifier(x) {
+                            self.diagnostics.push(self.source.error(self.lines[idx].span(), format!("Invalid macro parameter `{}`", x)));
+                        }
+                    }
+                    current = Some(Macro { name: name.to_owned(), params, line: idx, body: Vec::new() });
+                    *hide = true;
+                }
+                "endm" => {
+                    *hide = true;
+                    match current.take() {
+                        Some(x) if self.macros.contains_key(&x.name) => {
+                            let previous = self.lines[self.macros[&x.name].line].span();
+                            let error = self.source.error(self.lines[x.line].span(), format!("Macro `{}` is already defined", x.name))
+                                .with_note(self.source.note(previous, "previous definition is here".to_owned()));
+                            self.diagnostics.push(error);
+                        }
+                        Some(x) => {
+                            self.macros.insert(x.name.clone(), x);
+                        }
+                        None => self.diagnostics.push(self.source.error(self.lines[idx].span(), "`endm` without `macro`".to_owned())),
+                    }
+                }
+                _ => {
+                    if let Some(x) = &mut current {
+                        x.body.push(idx);
+                        *hide = true;
+                    }
+                }
+            }
+        }
+        if let Some(x) = current {
+            self.diagnostics.push(self.source.error(self.lines[x.line].span(), format!("Macro `{}` is not terminated with `endm`", x.name)));
+        }
+        hidden
+    }
+
+    /// Appends the line to the output, expanding it when it invokes a macro
+    fn emit(&mut self, idx: usize, text: String, invocations: Vec<Invocation>) {
+        let line = lines(&text).remove(0);
+        let (label, code) = split_label(&line.code);
+        let (word, args) = split_word(code);
+        let (params, body, macro_line) = match self.macros.get(word) {
+            Some(x) => (x.params.clone(), x.body.clone(), x.line),
+            None => {
+                let origin = self.origin(idx, &invocations);
+                let text = match self.comment.take() {
+                    // the comment of the invocation replaces the one of the line
+                    Some(comment) if !code.is_empty() => {
+                        let code = &text[..text.len() - line.comment.map_or(0, str::len)];
+                        format!("{} {}", code.trim_end(), comment)
+                    }
+                    comment => {
+                        self.comment = comment;
+                        text
+                    }
+                };
+                self.output.push(text, origin);
+                return;
+            }
+        };
+        let args = split_args(args);
+        if args.len() != params.len() {
+            let error = self.error(idx, &invocations, format!("Macro `{}` takes {} argument(s) but {} were supplied",
+                                                              word, params.len(), args.len()))
+                .with_note(self.source.note(self.lines[macro_line].span(), "macro is defined here".to_owned()));
+            self.diagnostics.push(error);
+            return;
+        }
+        if invocations.len() >= MAX_DEPTH {
+            // only the outermost invocation is shown, the rest repeats the same lines
+            let error = self.error(idx, &invocations[invocations.len() - 1..], format!("Macro `{}` is expanded recursively", word));
+            self.diagnostics.push(error);
+            return;
+        }
+        let origin = self.origin(idx, &invocations);
+        self.output.push_label(label, origin.clone());
+
+        let params: HashMap<&str, String> = params.iter().map(|x| x.as_str()).zip(args).collect();
+        let names: Vec<&str> = body.iter()
+            .filter_map(|x| split_label(&self.lines[*x].code).0)
+            .map(|label| &label[1..label.len() - 1])
+            .collect();
+        // skips numbers that would give a local label the name of a symbol written in the source
+        let suffix = loop {
+            self.counter += 1;
+            let suffix = format!("__{}", self.counter);
+            if names.iter().all(|x| !self.identifiers.contains(format!("{}{}", x, suffix).as_str())) {
+                break suffix;
+            }
+        };
+        let labels: HashMap<&str, String> = names.iter().map(|x| (*x, format!("{}{}", x, suffix))).collect();
+        let body: Vec<(usize, String)> = body.iter().map(|x| (*x, substitute(self.lines[*x].text, &params, &labels))).collect();
+        let mut nested = vec![Invocation { kind: InvocationKind::Macro(word.to_owned()), file: origin.file, offset: origin.offset }];
+        nested.extend(origin.invocations);
+        // a comment of an enclosing invocation is kept, it is the one written where the code is used
+        if self.comment.is_none() {
+            self.comment = line.comment.map(str::to_owned);
+        }
+        for (x, text) in body {
+            self.emit(x, text, nested.clone());
+        }
+        if invocations.is_empty() {
+            self.comment = None;
+        }
+    }
+}
+
+/// Expands macro definitions and invocations, the returned source maps locations back to the files
+pub fn expand(source: &Source) -> Result<Source, Diagnostics> {
+    let mut expander = Expander {
+        source,
+        lines: lines(&source.text),
+        macros: HashMap::new(),
+        counter: 0,
+        identifiers: HashSet::new(),
+        comment: None,
+        output: Output::default(),
+        diagnostics: Diagnostics::new(),
+    };
+    let hidden = expander.definitions();
+    if expander.macros.is_empty() && expander.diagnostics.is_empty() {
+        return Ok(source.clone());
+    }
+    expander.identifiers = expander.lines.iter()
+        .flat_map(|x| x.text.split(|c: char| !(is_word_char(c) || c == '.')))
+        .map(|x| x.trim_start_matches('.'))
+        .collect();
+    for (idx, hidden) in hidden.into_iter().enumerate() {
+        if hidden {
+            // definitions are kept as empty lines
+            expander.output.push(String::new(), source.line_origin(idx));
+        } else {
+            expander.emit(idx, expander.lines[idx].text.to_owned(), Vec::new());
+        }
+    }
+    if expander.diagnostics.has_errors() {
+        expander.diagnostics.sort();
+        return Err(expander.diagnostics);
+    }
+    Ok(expander.output.into_source(source.files()))
+}
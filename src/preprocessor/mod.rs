@@ -0,0 +1,183 @@
+//! Text transformations done before parsing: `include` and macro expansion.
+//! The resulting `Source` maps every line back to the file it was written in
+use std::path::{Path, PathBuf};
+use crate::diagnostic::{Diagnostics, LineOrigin, Source, SourceFile, Span};
+
+mod include;
+mod macros;
+
+/// Reads included files and expands macros, included files are searched next to the file including them
+/// and then in `paths`
+pub fn expand(source: &Source, paths: &[PathBuf]) -> Result<Source, Diagnostics> {
+    let source = include::expand(source, paths)?;
+    macros::expand(&source)
+}
+
+/// Finds a file named in `include` or `incbin` of the file `from`
+pub fn resolve(name: &str, from: &str, paths: &[PathBuf]) -> Option<PathBuf> {
+    let dir = Path::new(from).parent().unwrap_or_else(|| Path::new(""));
+    std::iter::once(dir)
+        .chain(paths.iter().map(PathBuf::as_path))
+        .map(|x| x.join(name))
+        .find(|x| x.is_file())
+}
+
+/// Text produced by a preprocessing step, with the origin of every line
+#[derive(Default)]
+struct Output {
+    text: Vec<String>,
+    origins: Vec<LineOrigin>,
+}
+
+impl Output {
+    fn push(&mut self, text: String, origin: LineOrigin) {
+        self.text.push(text);
+        self.origins.push(origin);
+    }
+
+    /// Starts the lines replacing a line with the label. The label stays on its own line,
+    /// so that it points to the first statement of the replacement
+    fn push_label(&mut self, label: Option<&str>, origin: LineOrigin) {
+        self.push(label.unwrap_or_default().to_owned(), origin);
+    }
+
+    fn into_source(self, files: Vec<SourceFile>) -> Source {
+        Source::expanded(files, self.text.join("\n"), self.origins)
+    }
+}
+
+/// Line of the text being preprocessed
+struct Line<'a> {
+    offset: usize,
+    text: &'a str,
+    /// Text without comments
+    code: String,
+    /// `;` or `//` comment ending the line, with the marker
+    comment: Option<&'a str>,
+}
+
+impl Line<'_> {
+    fn span(&self) -> Span {
+        line_span(self.offset, self.text)
+    }
+}
+
+/// Span of the line text without surrounding whitespace
+fn line_span(offset: usize, text: &str) -> Span {
+    let start = offset + text.len() - text.trim_start().len();
+    Span::new(start, start + text.trim().len())
+}
+
+/// Splits the text into lines, removing comments from the code so that directives can be recognized
+fn lines(text: &str) -> Vec<Line<'_>> {
+    let mut in_comment = false;
+    let mut offset = 0;
+    let mut result = Vec::new();
+    for text in text.split('\n') {
+        let mut code = String::new();
+        let mut comment = None;
+        let mut quote = None;
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let next = chars.peek().map(|(_, c)| *c);
+            if in_comment {
+                if c == '*' && next == Some('/') {
+                    chars.next();
+                    in_comment = false;
+                    code.push(' ');
+                }
+                continue;
+            }
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) if c == '\\' => {
+                    code.push(c);
+                    if let Some((_, c)) = chars.next() {
+                        code.push(c);
+                    }
+                    continue;
+                }
+                Some(_) => {}
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c == ';' || (c == '/' && next == Some('/')) => {
+                    comment = Some(text[i..].trim_end_matches('\r'));
+                    break;
+                }
+                None if c == '/' && next == Some('*') => {
+                    chars.next();
+                    in_comment = true;
+                    continue;
+                }
+                None => {}
+            }
+            code.push(c);
+        }
+        result.push(Line { offset, text, code, comment });
+        offset += text.len() + 1;
+    }
+    result
+}
+
+/// Splits `.label:` off the start of the code
+fn split_label(code: &str) -> (Option<&str>, &str) {
+    let code = code.trim();
+    if let Some(rest) = code.strip_prefix('.') {
+        if let Some(end) = rest.find(':') {
+            if is_identifier(&rest[..end]) {
+                return (Some(&code[..end + 2]), rest[end + 1..].trim_start());
+            }
+        }
+    }
+    (None, code)
+}
+
+/// Splits the first word off the code
+fn split_word(code: &str) -> (&str, &str) {
+    let code = code.trim();
+    let end = code.find(|c: char| c.is_whitespace()).unwrap_or(code.len());
+    (&code[..end], code[end..].trim())
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Splits macro arguments on commas outside of parentheses and quotes
+fn split_args(text: &str) -> Vec<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let mut args = vec![String::new()];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in text.chars() {
+        match quote {
+            _ if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    args.push(String::new());
+                    continue;
+                }
+                _ => {}
+            },
+        }
+        args.last_mut().unwrap().push(c);
+    }
+    args.into_iter().map(|x| x.trim().to_owned()).collect()
+}
+
+/// Text between double quotes
+fn quoted(text: &str) -> Option<&str> {
+    let text = text.trim().strip_prefix('"')?.strip_suffix('"')?;
+    (!text.contains('"')).then_some(text)
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use crate::diagnostic::Span;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SymbolKind {
+    // address of a statement
+    Label,
+    // `equ`, `=` or `lset`, cannot be redefined
+    Constant,
+    // `set`, can be redefined later in the file
+    Variable,
+    // predefined by the target, can be redefined by the program
+    Builtin,
+}
+
+impl SymbolKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymbolKind::Label => "label",
+            SymbolKind::Constant => "equ",
+            SymbolKind::Variable => "set",
+            SymbolKind::Builtin => "builtin",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub value: u16,
+    pub span: Span,
+}
+
+/// Labels and constants of a program. Symbols may be referenced both with and without the leading dot
+#[derive(Default)]
+pub struct SymbolTable {
+    symbols: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable { symbols: HashMap::new() }
+    }
+
+    fn key(name: &str) -> &str {
+        name.strip_prefix('.').unwrap_or(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.get(Self::key(name))
+    }
+
+    /// Adds the symbol, returning the location of the previous definition if it cannot be redefined
+    pub fn define(&mut self, name: &str, kind: SymbolKind, value: u16, span: Span) -> Result<(), Span> {
+        let key = Self::key(name);
+        match self.symbols.get_mut(key) {
+            Some(existing) if existing.kind == SymbolKind::Variable && kind == SymbolKind::Variable => {
+                existing.value = value;
+                Ok(())
+            }
+            Some(existing) if existing.kind == SymbolKind::Builtin => {
+                *existing = Symbol { name: key.to_owned(), kind, value, span };
+                Ok(())
+            }
+            Some(existing) => Err(existing.span),
+            None => {
+                self.symbols.insert(key.to_owned(), Symbol { name: key.to_owned(), kind, value, span });
+                Ok(())
+            }
+        }
+    }
+
+    /// All symbols defined by the program ordered by name
+    pub fn sorted(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = self.symbols.values().filter(|s| s.kind != SymbolKind::Builtin).cloned().collect();
+        symbols.sort_by(|a, b| a.name.cmp(&b.name));
+        symbols
+    }
+}
@@ -0,0 +1,96 @@
+use crate::compiler::DEFAULT_ORIGIN;
+
+/// Machine the program is assembled for
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Target {
+    /// KR580 training stand
+    Stand,
+    /// CP/M 2.2 transient program (.com)
+    Cpm,
+}
+
+/// Addresses of the CP/M zero page and BDOS function numbers, names follow the CP/M manuals
+const CPM_SYMBOLS: &[(&str, u16)] = &[
+    ("WBOOT", 0x0000),
+    ("IOBYTE", 0x0003),
+    ("CDISK", 0x0004),
+    ("BDOS", 0x0005),
+    ("FCB", 0x005C),
+    ("FCB2", 0x006C),
+    ("TBUFF", 0x0080),
+    ("TPA", 0x0100),
+    ("P_TERMCPM", 0),
+    ("C_READ", 1),
+    ("C_WRITE", 2),
+    ("A_READ", 3),
+    ("A_WRITE", 4),
+    ("L_WRITE", 5),
+    ("C_RAWIO", 6),
+    ("A_STATIN", 7),
+    ("A_STATOUT", 8),
+    ("C_WRITESTR", 9),
+    ("C_READSTR", 10),
+    ("C_STAT", 11),
+    ("S_BDOSVER", 12),
+    ("DRV_ALLRESET", 13),
+    ("DRV_SET", 14),
+    ("F_OPEN", 15),
+    ("F_CLOSE", 16),
+    ("F_SFIRST", 17),
+    ("F_SNEXT", 18),
+    ("F_DELETE", 19),
+    ("F_READ", 20),
+    ("F_WRITE", 21),
+    ("F_MAKE", 22),
+    ("F_RENAME", 23),
+    ("DRV_LOGINVEC", 24),
+    ("DRV_GET", 25),
+    ("F_DMAOFF", 26),
+    ("DRV_ALLOCVEC", 27),
+    ("DRV_SETRO", 28),
+    ("DRV_ROVEC", 29),
+    ("F_ATTRIB", 30),
+    ("DRV_DPB", 31),
+    ("F_USERNUM", 32),
+    ("F_READRAND", 33),
+    ("F_WRITERAND", 34),
+    ("F_SIZE", 35),
+    ("F_RANDREC", 36),
+    ("DRV_RESET", 37),
+    ("F_WRITEZF", 40),
+    // BIOS entries as offsets from the warm boot entry, whose address is stored at 0x0001
+    ("BIOS_WBOOT", 0),
+    ("BIOS_CONST", 3),
+    ("BIOS_CONIN", 6),
+    ("BIOS_CONOUT", 9),
+    ("BIOS_LIST", 12),
+    ("BIOS_PUNCH", 15),
+    ("BIOS_READER", 18),
+    ("BIOS_HOME", 21),
+    ("BIOS_SELDSK", 24),
+    ("BIOS_SETTRK", 27),
+    ("BIOS_SETSEC", 30),
+    ("BIOS_SETDMA", 33),
+    ("BIOS_READ", 36),
+    ("BIOS_WRITE", 39),
+    ("BIOS_LISTST", 42),
+    ("BIOS_SECTRAN", 45),
+];
+
+impl Target {
+    /// Address programs for the machine are loaded at
+    pub fn origin(&self) -> u16 {
+        match self {
+            Target::Stand => DEFAULT_ORIGIN,
+            Target::Cpm => 0x0100,
+        }
+    }
+
+    /// Symbols available to every program for the machine
+    pub fn symbols(&self) -> &'static [(&'static str, u16)] {
+        match self {
+            Target::Stand => &[],
+            Target::Cpm => CPM_SYMBOLS,
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use kr580compiler::{Compiler, Dialect, Target};
+
+#[test]
+fn target_sets_origin() {
+    let result = Compiler::new().target(Target::Cpm).assemble("mov c, C_WRITESTR\ncall BDOS\nret").unwrap();
+    assert_eq!(result.image().start(), Some(0x0100));
+    assert_eq!(result.bytes(), vec![0x0E, 0x09, 0xCD, 0x05, 0x00, 0xC9]);
+    let result = Compiler::new().origin(0x0200).target(Target::Cpm).assemble("ret").unwrap();
+    assert_eq!(result.image().start(), Some(0x0200));
+    assert_eq!(Compiler::new().assemble("ret").unwrap().image().start(), Some(Target::Stand.origin()));
+}
+
+#[test]
+fn nested_macros_get_own_local_labels() {
+    let source = "
+macro delay
+.loop:  dec b
+        jnz .loop
+endm
+macro wait n
+        mov b, n
+        delay
+endm
+.start: wait 3
+        wait 4
+        jmp .start
+";
+    let result = kr580compiler::assemble(source).unwrap();
+    assert_eq!(result.bytes(), vec![
+        0x06, 0x03, 0x05, 0xC2, 0x02, 0x82,
+        0x06, 0x04, 0x05, 0xC2, 0x08, 0x82,
+        0xC3, 0x00, 0x82,
+    ]);
+    assert_eq!(result.symbol("start").unwrap().value, 0x8200);
+}
+
+#[test]
+fn include_and_incbin_use_include_paths() {
+    let source = "
+        call .print
+        hlt
+        include \"lib.asm\"
+.data:  incbin \"data.bin\", 1, 2
+";
+    let result = Compiler::new().include_path("tests/include").assemble(source).unwrap();
+    assert_eq!(result.bytes(), vec![0xCD, 0x04, 0x82, 0x76, 0x0E, 0x09, 0xC9, 0x20, 0x30]);
+    assert_eq!(result.symbol("data").unwrap().value, 0x8207);
+}
+
+#[test]
+fn include_cycle_is_reported() {
+    let errors = kr580compiler::assemble("include \"tests/include/cycle_a.asm\"").unwrap_err();
+    let messages: Vec<&str> = errors.iter().map(|x| x.message.as_str()).collect();
+    assert_eq!(messages, vec!["File `cycle_a.asm` is included recursively"]);
+}
+
+#[test]
+fn conditions_select_branches() {
+    let source = "
+SIZE    equ 2
+        if SIZE > 1
+        mov a, 1
+        elif SIZE == 1
+        mov a, 2
+        else
+.never: mov a, 3
+        endif
+        ifdef FAST
+        mov b, 4
+        endif
+";
+    let result = kr580compiler::assemble(source).unwrap();
+    assert_eq!(result.bytes(), vec![0x3E, 0x01]);
+    assert!(result.symbol("never").is_none());
+    let result = Compiler::new().define("FAST", 1).assemble(source).unwrap();
+    assert_eq!(result.bytes(), vec![0x3E, 0x01, 0x06, 0x04]);
+}
+
+#[test]
+fn conditions_cannot_use_symbols_defined_below() {
+    let source = "
+        if LATER
+        mov a, 1
+        else
+        mov a, 2
+        endif
+LATER   equ 1
+";
+    let errors = kr580compiler::assemble(source).unwrap_err();
+    let messages: Vec<&str> = errors.iter().map(|x| x.message.as_str()).collect();
+    assert_eq!(messages, vec!["Symbol LATER is not defined, conditions can only use symbols defined above them"]);
+}
+
+#[test]
+fn data_directives_and_alignment() {
+    let source = r#"
+        align 256           ; already at a page boundary
+.first: db 1
+        align 256
+.page:  dbz "A\tB\x41\"", 'c'
+.buf:   ds 2, 0xFF
+        dw .page
+        dwbe .page
+"#;
+    let result = kr580compiler::assemble(source).unwrap();
+    assert_eq!(result.symbol("first").unwrap().value, 0x8200);
+    assert_eq!(result.symbol("page").unwrap().value, 0x8300);
+    assert_eq!(result.symbol("buf").unwrap().value, 0x8307);
+    let segments: Vec<(u16, &[u8])> = result.image().segments.iter().map(|x| (x.origin, x.data.as_slice())).collect();
+    assert_eq!(segments, vec![
+        (0x8200, &[0x01][..]),
+        (0x8300, &[0x41, 0x09, 0x42, 0x41, 0x22, 0x63, 0x00, 0xFF, 0xFF, 0x00, 0x83, 0x83, 0x00][..]),
+    ]);
+}
+
+#[test]
+fn only_undefined_symbols_are_explained() {
+    let messages = |source| kr580compiler::assemble(source).unwrap_err().iter().map(|x| x.message.clone()).collect::<Vec<_>>();
+    assert_eq!(messages("org 1/0\nret"), vec!["Division by zero"]);
+    assert_eq!(messages("org LATER\nret\nLATER equ 0x9000"), vec!["Symbol LATER is not defined, org can only use symbols defined above it"]);
+}
+
+#[test]
+fn overlapping_code_is_reported() {
+    let errors = kr580compiler::assemble("        mov a, 1\n        org 0x8201\n        mov b, 2\n").unwrap_err();
+    let errors: Vec<_> = errors.iter().collect();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "Code at 8201 overlaps with code placed earlier");
+    assert_eq!(errors[0].line, 3);
+    let notes: Vec<(&str, usize)> = errors[0].notes.iter().map(|x| (x.message.as_str(), x.line)).collect();
+    assert_eq!(notes, vec![("overlapping code is here", 1)]);
+}
+
+#[test]
+fn local_labels_named_like_keywords_keep_registers() {
+    let source = "
+macro c
+.c:     mov a, c
+        jmp .c
+endm
+macro twice
+.add:   add b
+        jnz .add
+endm
+        c
+        twice
+";
+    let result = kr580compiler::assemble(source).unwrap();
+    assert_eq!(result.bytes(), vec![0x79, 0xC3, 0x00, 0x82, 0x80, 0xC2, 0x04, 0x82]);
+}
+
+#[test]
+fn local_labels_do_not_clash_with_user_symbols() {
+    let source = "
+macro delay
+.loop:  dec b
+        jnz loop
+endm
+.loop__1: nop
+        delay
+        jmp .loop__1
+";
+    let result = kr580compiler::assemble(source).unwrap();
+    assert_eq!(result.symbol("loop__1").unwrap().value, 0x8200);
+    assert_eq!(result.symbol("loop__2").unwrap().value, 0x8201);
+    assert_eq!(result.bytes(), vec![0x00, 0x05, 0xC2, 0x01, 0x82, 0xC3, 0x00, 0x82]);
+}
+
+#[test]
+fn invocation_comment_goes_to_the_expanded_row() {
+    let source = "
+macro wait n
+.loop:                  ; counts down
+        mov b, n        ; load the counter
+        dec b
+endm
+        wait 3          ; short pause
+";
+    let result = kr580compiler::assemble(source).unwrap();
+    let comments: Vec<Option<&str>> = result.rows().iter().map(|x| x.comment.as_deref()).collect();
+    assert_eq!(comments, vec![Some("short pause"), None]);
+}
+
+#[test]
+fn dialect_selects_dw_byte_order() {
+    let source = "dw 0x1234\ndwle 0x1234\ndwbe 0x1234";
+    assert_eq!(kr580compiler::assemble(source).unwrap().bytes(), vec![0x34, 0x12, 0x34, 0x12, 0x12, 0x34]);
+    let result = Compiler::new().dialect(Dialect::Legacy).assemble(source).unwrap();
+    assert_eq!(result.bytes(), vec![0x12, 0x34, 0x34, 0x12, 0x12, 0x34]);
+}
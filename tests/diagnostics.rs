@@ -0,0 +1,52 @@
+use kr580compiler::assemble;
+
+#[test]
+fn error_points_at_the_operand() {
+    let errors = assemble("        mov a, 1\n        jmp nowhere\n").unwrap_err();
+    assert_eq!(errors.to_string(), "\
+error: Symbol nowhere is not defined
+ --> <input>:2:13
+  |
+2 |         jmp nowhere
+  |             ^^^^^^^
+
+error: aborting due to previous error
+");
+}
+
+#[test]
+fn macro_and_include_origins_are_noted() {
+    let source = "\
+macro load v
+        mov a, v
+endm
+        load 300
+        include \"tests/include/broken.asm\"
+";
+    let errors = assemble(source).unwrap_err();
+    assert_eq!(errors.to_string(), "\
+error: Value 300 does not fit into 8 bits
+ --> <input>:2:16
+  |
+2 |         mov a, v
+  |                ^
+note: in this expansion of macro `load`
+ --> <input>:4:9
+  |
+4 |         load 300
+  |         ^^^^^^^^
+
+error: Value 256 does not fit into 8 bits
+ --> tests/include/broken.asm:2:16
+  |
+2 |         mov a, 256
+  |                ^^^
+note: in the file included from here
+ --> <input>:5:9
+  |
+5 |         include \"tests/include/broken.asm\"
+  |         ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+
+error: aborting due to 2 previous errors
+");
+}
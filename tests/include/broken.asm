@@ -0,0 +1,2 @@
+; fails to compile, for the diagnostic tests
+        mov a, 256
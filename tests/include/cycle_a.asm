@@ -0,0 +1,2 @@
+; includes the file including it
+include "cycle_b.asm"
@@ -0,0 +1,2 @@
+.print: mov c, 9
+        ret
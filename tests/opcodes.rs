@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+use kr580compiler::{decode, OPCODES};
+
+const REGISTERS: [&str; 8] = ["a", "b", "c", "d", "e", "h", "l", "m"];
+const PAIRS: [&str; 4] = ["bc", "de", "hl", "sp"];
+
+/// Source with every statement the assembler can encode
+fn all_statements() -> Vec<String> {
+    let mut lines: Vec<String> = ["nop", "rlc", "rrc", "ral", "rar", "stc", "cmc", "daa", "hlt", "cli", "sti", "mov pc, hl", "neg a", "neg c",
+        "push psw", "pop psw", "in 0x12", "out 0x12", "mov a, [0x1234]", "mov [0x1234], a", "mov hl, [0x1234]", "mov [0x1234], hl",
+        "mov a, [bc]", "mov a, [de]", "mov [bc], a", "mov [de], a", "mov hl, [sp]", "mov [sp], hl", "mov sp, hl", "mov de, hl", "mov hl, de"]
+        .iter().map(|x| x.to_string()).collect();
+    for op in ["add", "adc", "sub", "sbb", "and", "or", "xor", "cmp"] {
+        lines.push(format!("{} 0x12", op));
+        lines.extend(REGISTERS.iter().map(|r| format!("{} {}", op, r)));
+    }
+    for op in ["jmp", "jz", "jnz", "jc", "jnc", "jpo", "jpe", "jp", "jm", "call", "cz", "cnz", "cc", "cnc", "cpo", "cpe", "cp", "cm"] {
+        lines.push(format!("{} 0x1234", op));
+    }
+    for op in ["ret", "rz", "rnz", "rc", "rnc", "rpo", "rpe", "rp", "rm"] {
+        lines.push(op.to_owned());
+    }
+    lines.extend((0..8).map(|n| format!("rst {}", n * 8)));
+    for r in REGISTERS {
+        lines.push(format!("inc {}", r));
+        lines.push(format!("dec {}", r));
+        lines.push(format!("mov {}, 0x12", r));
+        lines.extend(REGISTERS.iter().filter(|x| !(r == "m" && **x == "m")).map(|x| format!("mov {}, {}", r, x)));
+    }
+    for p in PAIRS {
+        lines.push(format!("inc {}", p));
+        lines.push(format!("dec {}", p));
+        lines.push(format!("dad {}", p));
+        lines.push(format!("mov {}, 0x1234", p));
+        if p != "sp" {
+            lines.push(format!("push {}", p));
+            lines.push(format!("pop {}", p));
+        }
+    }
+    lines
+}
+
+#[test]
+fn every_statement_round_trips() {
+    let lines = all_statements();
+    let result = kr580compiler::assemble(&lines.join("\n")).unwrap();
+    assert_eq!(result.rows().len(), lines.len());
+    let mut seen = HashSet::new();
+    for row in result.rows() {
+        let (text, len) = decode(&row.bytes).unwrap();
+        assert_eq!(text, row.asm(), "{}", row.source);
+        assert_eq!(len, row.bytes.len(), "{}", row.source);
+        assert_eq!(OPCODES[row.bytes[0] as usize].length as usize, len, "{}", row.source);
+        seen.insert(row.bytes[0]);
+    }
+    for x in OPCODES.iter().filter(|x| x.documented) {
+        assert!(seen.contains(&x.code), "{:02X} {} is not produced by any statement", x.code, x.mnemonic);
+    }
+}
+
+#[test]
+fn table_is_indexed_by_code() {
+    for (i, x) in OPCODES.iter().enumerate() {
+        assert_eq!(x.code as usize, i);
+    }
+}
+
+#[test]
+fn unsupported_combinations_fail() {
+    for line in ["mov m, m", "push sp", "pop sp", "neg b", "rst 3", "mov b, [0x1234]", "mov de, [0x1234]", "mov b, [bc]", "mov a, [hl]"] {
+        assert!(kr580compiler::assemble(line).is_err(), "{}", line);
+    }
+}
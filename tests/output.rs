@@ -0,0 +1,151 @@
+use kr580compiler::output::bin::{self, Layout};
+use kr580compiler::output::ihex;
+use kr580compiler::output::rk;
+use kr580compiler::output::rom::{self, Rom};
+use kr580compiler::output::srec;
+use kr580compiler::output::table::{self, Quoting};
+use kr580compiler::Image;
+
+#[test]
+fn raw_binary_starts_at_the_origin() {
+    let result = kr580compiler::assemble(".buf:   ds 4\n        mov a, 1\n").unwrap();
+    assert_eq!(bin::build(result.image(), Layout::Raw, 0xFF, result.origin()), vec![0xFF, 0xFF, 0xFF, 0xFF, 0x3E, 0x01]);
+    // code placed below the origin is kept
+    let result = kr580compiler::assemble("        mov a, 1\n        org 0x81FF\n        nop\n").unwrap();
+    assert_eq!(bin::build(result.image(), Layout::Raw, 0xFF, result.origin()), vec![0x00, 0x3E, 0x01]);
+}
+
+#[test]
+fn full_and_window_layouts() {
+    let result = kr580compiler::assemble("        mov a, 1\n        ret\n").unwrap();
+    let full = bin::build(result.image(), Layout::Full, 0xFF, result.origin());
+    assert_eq!(full.len(), 0x10000);
+    assert_eq!(&full[0x81FF..0x8204], &[0xFF, 0x3E, 0x01, 0xC9, 0xFF]);
+    assert!(!bin::is_truncated(result.image(), Layout::Full, result.origin()));
+    let window = Layout::Window(0x8201, 0x8203);
+    assert_eq!(bin::build(result.image(), window, 0xFF, result.origin()), vec![0x01, 0xC9, 0xFF]);
+    assert!(bin::is_truncated(result.image(), window, result.origin()));
+    assert!(!bin::is_truncated(result.image(), Layout::Window(0x8200, 0x8202), result.origin()));
+}
+
+#[test]
+fn intel_hex_round_trips() {
+    let result = kr580compiler::assemble("        mov a, 1\n        mov b, 2\n        ret\n        org 0x9000\n        db 1, 2\n").unwrap();
+    let text = ihex::write(result.image(), Some(0x8204), 4);
+    assert_eq!(text, "\
+:048200003E01060233
+:01820400C9B0
+:0290000001026B
+:040000030000820473
+:00000001FF
+");
+    let (image, entry) = ihex::read(&text).unwrap();
+    let segments = |image: &Image| image.segments.iter().map(|x| (x.origin, x.data.clone())).collect::<Vec<_>>();
+    assert_eq!(segments(&image), segments(result.image()));
+    assert_eq!(entry, Some(0x8204));
+}
+
+#[test]
+fn intel_hex_errors_are_reported() {
+    assert_eq!(ihex::read(":0100000001FF\n").unwrap_err(), "line 1: checksum mismatch");
+    assert_eq!(ihex::read(":aé00000000000\n").unwrap_err(), "line 1: record contains characters other than hex digits");
+}
+
+#[test]
+fn s_records_have_header_count_and_entry() {
+    let result = kr580compiler::assemble("        mov a, 1\n        mov b, 2\n        ret\n").unwrap();
+    assert_eq!(srec::write(result.image(), Some(0x8200), 4, "test"), "\
+S00700007465737438
+S10782003E0106022F
+S1048204C9AC
+S5030002FA
+S90382007A
+");
+}
+
+#[test]
+fn rom_files_pack_words_little_endian() {
+    let result = kr580compiler::assemble("        mov a, 1\n        mov b, 2\n        ret\n").unwrap();
+    let rom = Rom { width: 16, depth: Some(4), base: None };
+    assert_eq!(rom::mem(&rom, result.image(), 0xFF).unwrap(), "013E\n0206\nFFC9\nFFFF\n");
+    assert_eq!(rom::mif(&rom, result.image(), 0xFF).unwrap(), "\
+WIDTH=16;
+DEPTH=4;
+
+ADDRESS_RADIX=HEX;
+DATA_RADIX=HEX;
+
+CONTENT BEGIN
+    0 : 013E;
+    1 : 0206;
+    2 : FFC9;
+    3 : FFFF;
+END;
+");
+    assert_eq!(rom::coe(&rom, result.image(), 0xFF).unwrap(), "\
+memory_initialization_radix=16;
+memory_initialization_vector=
+013E,
+0206,
+FFC9,
+FFFF;
+");
+    let rom = Rom { width: 8, depth: Some(0x10000), base: Some(1) };
+    assert_eq!(rom::mem(&rom, result.image(), 0).unwrap_err(), "ROM of 65536 words of 8 bits at 0001 does not fit into the 64 KB address space");
+}
+
+#[test]
+fn tape_checksums() {
+    assert_eq!(rk::checksum(&[]), 0);
+    assert_eq!(rk::checksum(&[0x01, 0x02, 0x03]), 0x0306);
+    // carries from the low byte go into the high one, the last byte does not carry
+    assert_eq!(rk::checksum(&[0x80, 0x80, 0x00]), 0x0100);
+    assert_eq!(rk::checksum(&[0x10, 0xF5]), 0x1005);
+    assert_eq!(rk::checksum_mikrosha(&[0x01, 0x02, 0x03]), 0x0202);
+    assert_eq!(rk::checksum_mikrosha(&[0xF0, 0x0F, 0x0F, 0xF0]), 0xFFFF);
+}
+
+#[test]
+fn tape_files_round_trip() {
+    let result = kr580compiler::assemble("        mov a, 1\n        ret\n").unwrap();
+    let file = rk::build(result.image(), 0, false).unwrap();
+    assert_eq!(file, vec![0x82, 0x00, 0x82, 0x02, 0x3E, 0x01, 0xC9, 0x00, 0x00, 0xE6, 0x3F, 0x08]);
+    assert_eq!(rk::read(&file, false).unwrap().segments[0].data, result.bytes());
+    let file = rk::build(result.image(), 0, true).unwrap();
+    assert_eq!(file[10..], [0x01, 0xF7]);
+    assert_eq!(rk::read(&file, true).unwrap().segments[0].data, result.bytes());
+    assert_eq!(rk::read(&file, false).unwrap_err(), "checksum mismatch: 3F08 != 01F7");
+}
+
+const TABLE_SOURCE: &str = ".loop:  mov a, 1    ; a;b \"c\" <d>|e\n        jmp .loop\n";
+
+#[test]
+fn csv_quotes_fields_with_the_delimiter() {
+    let result = kr580compiler::assemble(TABLE_SOURCE).unwrap();
+    assert_eq!(table::csv(result.rows(), ';', Quoting::Minimal), "\
+ADDRESS;CODE;LABEL;ASM;COMMENT
+8200;3E 01;loop;mvi a, 01;\"a;b \"\"c\"\" <d>|e\"
+8202;C3 00 82;;jmp loop;
+");
+    assert_eq!(table::csv(result.rows(), ',', Quoting::Minimal), "\
+ADDRESS,CODE,LABEL,ASM,COMMENT
+8200,3E 01,loop,\"mvi a, 01\",\"a;b \"\"c\"\" <d>|e\"
+8202,C3 00 82,,jmp loop,
+");
+    assert_eq!(table::csv(result.rows(), ',', Quoting::Always).lines().nth(2), Some("\"8202\",\"C3 00 82\",\"\",\"jmp loop\",\"\""));
+    assert_eq!(table::csv(result.rows(), '\t', Quoting::Never).lines().nth(1), Some("8200\t3E 01\tloop\tmvi a, 01\ta;b \"c\" <d>|e"));
+}
+
+#[test]
+fn json_markdown_and_html_tables_escape_text() {
+    let result = kr580compiler::assemble(TABLE_SOURCE).unwrap();
+    assert_eq!(table::json(result.rows()), "[
+  {\"address\": 33280, \"bytes\": [62, 1], \"label\": \"loop\", \"mnemonic\": \"mvi\", \"operands\": \"a, 01\", \"comment\": \"a;b \\\"c\\\" <d>|e\", \"line\": 1, \"source\": \".loop:  mov a, 1    ; a;b \\\"c\\\" <d>|e\"},
+  {\"address\": 33282, \"bytes\": [195, 0, 130], \"label\": null, \"mnemonic\": \"jmp\", \"operands\": \"loop\", \"comment\": null, \"line\": 2, \"source\": \"        jmp .loop\"}
+]
+");
+    assert_eq!(table::markdown(result.rows()).lines().nth(2), Some("| 8200 | 3E 01 | loop | mvi a, 01 | a;b \"c\" <d>\\|e |"));
+    let html = table::html(result.rows(), "a<b");
+    assert!(html.contains("<title>a&lt;b</title>"));
+    assert!(html.contains("<tr><td>8200</td><td>3E 01</td><td>loop</td><td>mvi a, 01</td><td>a;b &quot;c&quot; &lt;d&gt;|e</td></tr>\n"));
+}
@@ -0,0 +1,13 @@
+use kr580compiler::{parse, Span};
+
+#[test]
+fn independent_syntax_errors_are_all_reported() {
+    let source = "mov a, 1\nmov b, @3\nmov c, 2\nadd a, b\nmov d, 0x10000\n";
+    let errors = parse(source).unwrap_err();
+    let found: Vec<(usize, usize, Span, &str)> = errors.iter().map(|x| (x.line, x.column, x.span, x.message.as_str())).collect();
+    assert_eq!(found, vec![
+        (2, 8, Span::new(16, 17), "Unknown character `@`"),
+        (4, 6, Span::new(33, 34), "Unexpected `,`, expected one of end of line, label"),
+        (5, 8, Span::new(44, 51), "Number 0x10000 does not fit into 16 bits"),
+    ]);
+}
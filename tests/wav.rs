@@ -0,0 +1,30 @@
+use kr580compiler::output::{rk, wav};
+
+/// Program of two blocks, so that the tape also carries the gap between them
+const PROGRAM: &str = "mov a, 0x55\nmov b, 0xAA\ncall 0x8300\nhlt\norg 0x8300\nmov c, 0xFF\nret\n";
+const SPEEDS: [(u32, u32); 4] = [(1200, 44100), (2400, 44100), (1200, 22050), (300, 8000)];
+
+#[test]
+fn rk_recording_round_trips() {
+    let result = kr580compiler::assemble(PROGRAM).unwrap();
+    for (baud, sample_rate) in SPEEDS {
+        let recording = wav::encode(&wav::tape(result.image(), 0).unwrap(), baud, sample_rate);
+        let image = wav::read(&recording).unwrap_or_else(|e| panic!("{} baud at {} Hz: {}", baud, sample_rate, e));
+        assert_eq!(image.start(), result.image().start(), "{} baud at {} Hz", baud, sample_rate);
+        assert_eq!(image.segments[0].data, result.bytes(), "{} baud at {} Hz", baud, sample_rate);
+    }
+}
+
+#[test]
+fn rkm_recording_round_trips() {
+    let result = kr580compiler::assemble(PROGRAM).unwrap();
+    let file = rk::build(result.image(), 0, true).unwrap();
+    // pilot of zero bytes and the sync byte, as written by the monitor
+    let mut tape = vec![0; 256];
+    tape.push(0xE6);
+    tape.extend_from_slice(&file);
+    for (baud, sample_rate) in SPEEDS {
+        let decoded = wav::decode(&wav::encode(&tape, baud, sample_rate)).unwrap_or_else(|e| panic!("{} baud at {} Hz: {}", baud, sample_rate, e));
+        assert_eq!(decoded, file, "{} baud at {} Hz", baud, sample_rate);
+    }
+}